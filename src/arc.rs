@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use crate::double_link::{QcDoubleLink, QcTd};
+use crate::replacer::{FrameId, Replacer};
+use crate::trace::PageId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QcArcList {
+    T1,
+    T2,
+}
+
+#[derive(Debug)]
+struct QcArcNode {
+    page_id: PageId,
+    list: QcArcList,
+    td: QcTd<FrameId>,
+    evictable: bool,
+}
+
+// -- ARC: T1/T2 为驻留的frame, B1/B2 为被淘汰page的ghost
+//      p 为T1的目标大小, 命中B1时增大, 命中B2时减小
+//      队列头为最久未用, 队列尾为最近使用
+#[derive(Debug)]
+pub struct QcArcReplacer {
+    capacity: usize,
+    p: usize,
+    evictable_num: usize,
+    t1: QcDoubleLink<FrameId>,
+    t2: QcDoubleLink<FrameId>,
+    b1: QcDoubleLink<PageId>,
+    b2: QcDoubleLink<PageId>,
+    alive: HashMap<FrameId, QcArcNode>,
+    ghosts: HashMap<PageId, (QcArcList, QcTd<PageId>)>,
+}
+
+impl QcArcReplacer {
+    pub fn new(capacity: usize) -> Self {
+        QcArcReplacer {
+            capacity,
+            p: 0,
+            evictable_num: 0,
+            t1: QcDoubleLink::new(),
+            t2: QcDoubleLink::new(),
+            b1: QcDoubleLink::new(),
+            b2: QcDoubleLink::new(),
+            alive: HashMap::new(),
+            ghosts: HashMap::new(),
+        }
+    }
+
+    pub fn target(&self) -> usize {
+        return self.p;
+    }
+
+    pub fn report(&self) {
+        println!("p: {}", self.p);
+        print!("t1: ");
+        self.t1.report();
+        print!("t2: ");
+        self.t2.report();
+        print!("b1: ");
+        self.b1.report();
+        print!("b2: ");
+        self.b2.report();
+    }
+
+    // -- 淘汰ghost队列中最旧的page
+    fn pop_ghost(&mut self, which: QcArcList) {
+        let old = match which {
+            QcArcList::T1 => self.b1.pop_front(),
+            QcArcList::T2 => self.b2.pop_front(),
+        };
+        if let Some(pid) = old {
+            self.ghosts.remove(&pid);
+        }
+    }
+
+    // -- 从队头找第一个可淘汰的frame
+    fn evict_from(&mut self, which: QcArcList) -> Option<FrameId> {
+        let alive = &self.alive;
+        let list = match which {
+            QcArcList::T1 => &mut self.t1,
+            QcArcList::T2 => &mut self.t2,
+        };
+
+        let td = list.find(|fid| alive[fid].evictable);
+        let Some(fid) = list.remove_item(td) else {
+            return None;
+        };
+
+        let nd = self.alive.remove(&fid).unwrap();
+        self.evictable_num -= 1;
+
+        let gtd = match which {
+            QcArcList::T1 => self.b1.push_back(nd.page_id),
+            QcArcList::T2 => self.b2.push_back(nd.page_id),
+        };
+        self.ghosts.insert(nd.page_id, (which, gtd));
+
+        return Some(fid);
+    }
+}
+
+// -- alive/ghosts 中的节点指针只指向自己的队列, 一起转移
+unsafe impl Send for QcArcReplacer {}
+
+impl Replacer for QcArcReplacer {
+    fn record_access(&mut self, frame_id: FrameId, page_id: PageId) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        // -- 命中T1/T2: 移到T2尾
+        if let Some(nd) = self.alive.get_mut(&frame_id) {
+            match nd.list {
+                QcArcList::T1 => self.t1.remove_item(nd.td),
+                QcArcList::T2 => self.t2.remove_item(nd.td),
+            };
+            nd.list = QcArcList::T2;
+            nd.td = self.t2.push_back(frame_id);
+            return;
+        }
+
+        let list = match self.ghosts.remove(&page_id) {
+            Some((QcArcList::T1, gtd)) => {
+                // -- 命中B1: 偏向recency
+                self.b1.remove_item(gtd);
+                let delta = (self.b2.len() / (self.b1.len() + 1)).max(1);
+                self.p = (self.p + delta).min(self.capacity);
+                QcArcList::T2
+            }
+            Some((QcArcList::T2, gtd)) => {
+                // -- 命中B2: 偏向frequency
+                self.b2.remove_item(gtd);
+                let delta = (self.b1.len() / (self.b2.len() + 1)).max(1);
+                self.p = self.p.saturating_sub(delta);
+                QcArcList::T2
+            }
+            None => {
+                // -- 全未命中: 控制ghost的长度
+                if self.t1.len() + self.b1.len() >= self.capacity {
+                    self.pop_ghost(QcArcList::T1);
+                } else if self.t1.len() + self.t2.len() + self.b1.len() + self.b2.len() >= 2 * self.capacity {
+                    self.pop_ghost(QcArcList::T2);
+                }
+                QcArcList::T1
+            }
+        };
+
+        let td = match list {
+            QcArcList::T1 => self.t1.push_back(frame_id),
+            QcArcList::T2 => self.t2.push_back(frame_id),
+        };
+        self.alive.insert(frame_id, QcArcNode {
+            page_id,
+            list,
+            td,
+            evictable: false,
+        });
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        let Some(nd) = self.alive.get_mut(&frame_id) else {
+            return;
+        };
+
+        if nd.evictable != evictable {
+            nd.evictable = evictable;
+            if evictable {
+                self.evictable_num += 1;
+            } else {
+                self.evictable_num -= 1;
+            }
+        }
+    }
+
+    fn evict(&mut self) -> Option<FrameId> {
+        let (first, second) = if self.t1.len() >= self.p.max(1) {
+            (QcArcList::T1, QcArcList::T2)
+        } else {
+            (QcArcList::T2, QcArcList::T1)
+        };
+
+        if let Some(fid) = self.evict_from(first) {
+            return Some(fid);
+        }
+
+        return self.evict_from(second);
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        let Some(nd) = self.alive.remove(&frame_id) else {
+            return;
+        };
+
+        match nd.list {
+            QcArcList::T1 => self.t1.remove_item(nd.td),
+            QcArcList::T2 => self.t2.remove_item(nd.td),
+        };
+        if nd.evictable {
+            self.evictable_num -= 1;
+        }
+    }
+
+    fn size(&self) -> usize {
+        return self.evictable_num;
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 #[derive(Debug)]
 pub struct Qcbitmap(Vec<u8>);
 
@@ -17,6 +19,22 @@ impl Qcbitmap {
         Some(())
     }
 
+    pub fn clear(&mut self, idx: usize) -> Option<()> {
+        let block_num = idx / 8;
+        let block_offset = idx % 8;
+
+        self.0[block_num] &= !(0b10000000 >> block_offset);
+
+        Some(())
+    }
+
+    pub fn get(&self, idx: usize) -> bool {
+        let block_num = idx / 8;
+        let block_offset = idx % 8;
+
+        self.0[block_num] & (0b10000000 >> block_offset) != 0
+    }
+
     pub fn issue(&self) -> Option<usize> {
         for (ox, &ob) in self.0.iter().enumerate() {
             let mut ci = 0_usize;
@@ -42,3 +60,60 @@ impl Qcbitmap {
         println!();
     }
 }
+
+// -- 可并发使用的bitmap, 用作frame分配器
+//      issue_set 原子地找到一个0位并置1
+#[derive(Debug)]
+pub struct QcAtomicBitmap {
+    size: usize,
+    blocks: Vec<AtomicU64>,
+}
+
+impl QcAtomicBitmap {
+    pub fn new(size: usize) -> Self {
+        let num = size.div_ceil(64);
+
+        QcAtomicBitmap {
+            size,
+            blocks: (0..num).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    pub fn issue_set(&self) -> Option<usize> {
+        for (ox, blk) in self.blocks.iter().enumerate() {
+            let mut cur = blk.load(Ordering::Acquire);
+            loop {
+                let ci = (!cur).leading_zeros() as usize;
+                let idx = ox * 64 + ci;
+                if ci >= 64 || idx >= self.size {
+                    break;
+                }
+
+                let bit = (1_u64 << 63) >> ci;
+                match blk.compare_exchange_weak(cur, cur | bit, Ordering::AcqRel, Ordering::Acquire) {
+                    Ok(_) => return Some(idx),
+                    Err(now) => cur = now,
+                }
+            }
+        }
+
+        return None;
+    }
+
+    pub fn clear(&self, idx: usize) {
+        let bit = (1_u64 << 63) >> (idx % 64);
+        self.blocks[idx / 64].fetch_and(!bit, Ordering::AcqRel);
+    }
+
+    pub fn get(&self, idx: usize) -> bool {
+        let bit = (1_u64 << 63) >> (idx % 64);
+        self.blocks[idx / 64].load(Ordering::Acquire) & bit != 0
+    }
+
+    pub fn report(&self) {
+        for blk in self.blocks.iter() {
+            print!("{:#066b}, ", blk.load(Ordering::Relaxed));
+        }
+        println!();
+    }
+}
@@ -1,99 +1,348 @@
-use std::{collections::HashMap, fmt::Error, fs::{File, OpenOptions}, os::unix::fs::{FileExt, OpenOptionsExt}, sync::{Arc, Mutex, Weak}};
+use std::{collections::VecDeque, io, ops::Range, sync::{atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, Arc, Mutex, MutexGuard, RwLock, TryLockError, Weak}};
 
-use crate::{bitmap::Qcbitmap, error::QcBupoError, page::QcPager, trace::PageId};
+use crate::{bitmap::QcAtomicBitmap, checksum, checkpoint::{self, QcCheckpointer}, cleaner::{QcCleanerSignal, QcPageCleaner}, config::{QcBuffpoolConfig, QcSyncPolicy}, disk::{DiskManager, HEADER_PAGE_ID}, error::QcBupoError, page::QcPager, page_guard::{ReadPageGuard, WritePageGuard}, page_table::{QcBuffItem, QcPageTable, QcShard}, read_ahead::QcReadAhead, recovery::{QcRecovery, QcRecoveryReport}, replacer::{FrameId, Replacer}, strategy::{QcAccessStrategy, QcStrategyKind}, trace::PageId, wal::{Lsn, QcLogBody, QcLogManager, INVALID_LSN, INVALID_TXN}};
 
-#[derive(Debug)]
-struct QcBuffItem {
-    frame_id: usize,
-    ref_num: i32,
-}
-
-impl QcBuffItem {
-    pub fn new(frame_id: usize, ref_num: i32) -> Self {
-        return QcBuffItem{
-            frame_id,
-            ref_num,
-        };
-    }
+// -- pool 与 cleaner 线程共享的部分
+//      锁顺序: miss -> table分片 -> replacer, frame锁只在分片锁之后获取
+pub(crate) struct QcPoolInner {
+    frame: Vec<Arc<RwLock<QcPager>>>,        // 读写latch, 只读访问可并发
+    frame_bits: QcAtomicBitmap,         // 已被占用的frame
+    frame_page: Vec<AtomicU64>,         // <frame_id> => <page_id>, 空闲为INVALID
+    table: QcPageTable,
+    replacer: Mutex<Box<dyn Replacer>>,
+    miss: Mutex<()>,                    // 串行化装入/分配/删除page, 命中与unpin不经过它
+    storage: Arc<dyn DiskManager>,     // 并行pool的各实例共享
+    log: Option<Arc<QcLogManager>>,     // 预写日志, 同样共享
+    sync: QcSyncPolicy,
+    dirty_hint: Vec<AtomicBool>,        // write guard 释放时记录, 写回后清除
+    rec_lsn: Vec<AtomicU64>,            // frame被修改前的日志末尾, 干净为INVALID_LSN; 即checkpoint的脏页表
+    dirty_num: AtomicUsize,
+    dirty_watermark: Option<usize>,
+    signal: QcCleanerSignal,
+    read_ahead: usize,                  // 顺序预读窗口, 0为关闭
+    seq_streams: Mutex<Vec<(u64, usize)>>,  // 各顺序流最近访问的page id与连续访问次数
+    ahead_reqs: Mutex<VecDeque<PageId>>,    // 等待预读线程读入的窗口起点
+    ahead_signal: QcCleanerSignal,
 }
 
 pub struct QcBuffpool {
-    frame: Vec<Arc<Mutex<QcPager>>>,
-    frame_bits: Qcbitmap,
-    table: HashMap<PageId, QcBuffItem>,
-    storage: Box<File>,
+    inner: Arc<QcPoolInner>,
+    cleaner: Option<QcPageCleaner>,
+    reader: Option<QcReadAhead>,
+    recovery: Option<QcRecoveryReport>,
+    checkpointer: Option<QcCheckpointer>,
 }
 
 impl QcBuffpool {
-    pub fn new(size: usize) -> Self {
-        use std::fs::OpenOptions;
-        let fd = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("tmp_buffer.db").unwrap();
+    // -- 默认配置: tmp_buffer.db, LRU
+    pub fn new(size: usize) -> Result<Self, QcBupoError> {
+        return QcBuffpoolConfig::default().pool_size(size).build();
+    }
 
-        fd.set_len(4096 * 16).unwrap();
+    pub fn with_replacer(size: usize, replacer: Box<dyn Replacer>) -> Result<Self, QcBupoError> {
+        let config = QcBuffpoolConfig::default().pool_size(size);
+        config.validate(false)?;
+        let storage = config.open_storage()?;
+        return Self::open(&config, replacer, storage, None);
+    }
 
+    pub(crate) fn open(config: &QcBuffpoolConfig, replacer: Box<dyn Replacer>, storage: Arc<dyn DiskManager>, log: Option<Arc<QcLogManager>>) -> Result<Self, QcBupoError> {
+        let size = config.pool_size;
         let mut bf = Vec::new();
         for _ in 0..size {
-            bf.push(Arc::new(Mutex::new(QcPager::new())))
+            bf.push(Arc::new(RwLock::new(QcPager::new())))
         }
 
-        return QcBuffpool {
+        let inner = Arc::new(QcPoolInner {
             frame: bf,
-            frame_bits: Qcbitmap::new(size),
-            table: HashMap::new(),
-            storage: Box::new(fd),
+            frame_bits: QcAtomicBitmap::new(size),
+            frame_page: (0..size).map(|_| AtomicU64::new(PageId::INVALID.0)).collect(),
+            table: QcPageTable::new(),
+            replacer: Mutex::new(replacer),
+            miss: Mutex::new(()),
+            storage,
+            log,
+            sync: config.sync,
+            dirty_hint: (0..size).map(|_| AtomicBool::new(false)).collect(),
+            rec_lsn: (0..size).map(|_| AtomicU64::new(INVALID_LSN)).collect(),
+            dirty_num: AtomicUsize::new(0),
+            dirty_watermark: config.cleaner
+                .map(|cc| ((size as f64 * cc.dirty_watermark).ceil() as usize).max(1)),
+            signal: QcCleanerSignal::default(),
+            read_ahead: config.read_ahead,
+            seq_streams: Mutex::new(vec![(PageId::INVALID.0, 0); QcPoolInner::SEQ_STREAMS]),
+            ahead_reqs: Mutex::new(VecDeque::new()),
+            ahead_signal: QcCleanerSignal::default(),
+        });
+
+        let cleaner = config.cleaner
+            .map(|cc| QcPageCleaner::spawn(Arc::clone(&inner), cc));
+        let reader = (config.read_ahead > 0)
+            .then(|| QcReadAhead::spawn(Arc::clone(&inner)));
+
+        return Ok(QcBuffpool {
+            inner,
+            cleaner,
+            reader,
+            recovery: None,
+            checkpointer: None,
+        });
+    }
+
+    // -- 配置了日志时, 打开后先从上一个checkpoint做崩溃恢复, 再按配置启动checkpoint线程
+    pub(crate) fn recover_on_open(mut self, config: &QcBuffpoolConfig) -> Result<Self, QcBupoError> {
+        let Some(log) = self.inner.log.clone() else {
+            return Ok(self);
         };
+
+        let ckpt = self.inner.storage.checkpoint_lsn()?;
+        let report = QcRecovery::new(&log, ckpt).run(|pid| self.fetch_page_write(pid))?;
+        self.recovery = Some(report);
+
+        self.checkpointer = config.checkpoint
+            .map(|cc| QcCheckpointer::spawn(vec![Arc::clone(&self.inner)], cc));
+
+        return Ok(self);
     }
 
-    // FIXME
-    pub fn fetch_page(&mut self, page_id: PageId) -> Weak<Mutex<QcPager>> {
-        if let Some(pgi) = self.table.get_mut(&page_id) {
-            pgi.ref_num += 1;
-            return Arc::downgrade(&self.frame[pgi.frame_id]);
-        } else {
-            let Some(npgid) = self.frame_bits.issue() else {
-                panic!("fill pool");
-            };
-            self.frame_bits.set(npgid);
+    // -- 立即做一次fuzzy checkpoint, 返回其begin LSN; 没有日志时返回INVALID_LSN
+    pub fn checkpoint(&self) -> Result<Lsn, QcBupoError> {
+        return checkpoint::checkpoint(std::slice::from_ref(&self.inner));
+    }
 
-            let mut tmp_pg = QcPager::new();
+    pub(crate) fn inner(&self) -> &Arc<QcPoolInner> {
+        &self.inner
+    }
 
-            self.table.insert(page_id, QcBuffItem::new(npgid, 1));
-            self.storage.read_at(
-                tmp_pg.mut_buffer(), 
-                4096 * (page_id as u64),
-            ).unwrap();
+    // -- 打开时恢复的结果, 没有日志则为None
+    pub fn recovery_report(&self) -> Option<&QcRecoveryReport> {
+        self.recovery.as_ref()
+    }
 
-            if tmp_pg.is_valiable() {
-                *(self.frame[npgid].lock().unwrap()) = tmp_pg;
+    // -- 获取page, 并pin住; 用完需调用unpin_page
+    pub fn fetch_page(&self, page_id: PageId) -> Result<Weak<RwLock<QcPager>>, QcBupoError> {
+        let fid = self.inner.pin_page(page_id)?;
+        return Ok(Arc::downgrade(&self.inner.frame[fid]));
+    }
+
+    // -- 获取page并加读锁, guard drop时自动unpin
+    pub fn fetch_page_read(&self, page_id: PageId) -> Result<ReadPageGuard<'_>, QcBupoError> {
+        let fid = self.inner.pin_page(page_id)?;
+        return Ok(ReadPageGuard::new(self, page_id, self.inner.frame[fid].read().unwrap()));
+    }
+
+    // -- 获取page并加写锁, guard drop时自动unpin
+    pub fn fetch_page_write(&self, page_id: PageId) -> Result<WritePageGuard<'_>, QcBupoError> {
+        let fid = self.inner.pin_page(page_id)?;
+        return Ok(WritePageGuard::new(self, page_id, fid, self.inner.frame[fid].write().unwrap()));
+    }
+
+    // -- 按访问策略读取: 未命中时复用策略ring中的frame
+    pub fn fetch_page_read_with(&self, page_id: PageId, strategy: &mut QcAccessStrategy) -> Result<ReadPageGuard<'_>, QcBupoError> {
+        let fid = self.inner.pin_page_with(page_id, Some(strategy))?;
+        return Ok(ReadPageGuard::new(self, page_id, self.inner.frame[fid].read().unwrap()));
+    }
+
+    pub fn fetch_page_write_with(&self, page_id: PageId, strategy: &mut QcAccessStrategy) -> Result<WritePageGuard<'_>, QcBupoError> {
+        let fid = self.inner.pin_page_with(page_id, Some(strategy))?;
+        return Ok(WritePageGuard::new(self, page_id, fid, self.inner.frame[fid].write().unwrap()));
+    }
+
+    // -- 同fetch_page_read, 但frame已被写锁住时不等待, 返回LatchBusy
+    pub fn try_fetch_page_read(&self, page_id: PageId) -> Result<ReadPageGuard<'_>, QcBupoError> {
+        let fid = self.inner.pin_page(page_id)?;
+        let latch = match self.inner.frame[fid].try_read() {
+            Ok(latch) => latch,
+            Err(TryLockError::WouldBlock) => {
+                self.unpin_page(page_id);
+                return Err(QcBupoError::LatchBusy);
             }
+            Err(TryLockError::Poisoned(e)) => panic!("{e}"),
+        };
 
-            return Arc::downgrade(&self.frame[npgid]);
-        }
+        return Ok(ReadPageGuard::new(self, page_id, latch));
     }
 
-    // FIXME
-    pub fn flush_page(&mut self, page_id: PageId) -> Result<(), QcBupoError> {
-        if let Some(pgi) = self.table.get_mut(&page_id) {
-            return if Arc::strong_count(&self.frame[pgi.frame_id]) > 1 {
-            // return if pgi.ref_num > 0 {
-                Err(QcBupoError)
-            } else {
-                self.storage.write_at(
-                    self.frame[pgi.frame_id].lock().unwrap().buffer(), 
-                    4096 * (page_id as u64),
-                ).unwrap();
+    // -- 同fetch_page_write, 但frame已被锁住时不等待, 返回LatchBusy
+    pub fn try_fetch_page_write(&self, page_id: PageId) -> Result<WritePageGuard<'_>, QcBupoError> {
+        let fid = self.inner.pin_page(page_id)?;
+        let latch = match self.inner.frame[fid].try_write() {
+            Ok(latch) => latch,
+            Err(TryLockError::WouldBlock) => {
+                self.unpin_page(page_id);
+                return Err(QcBupoError::LatchBusy);
+            }
+            Err(TryLockError::Poisoned(e)) => panic!("{e}"),
+        };
+
+        return Ok(WritePageGuard::new(self, page_id, fid, latch));
+    }
 
-                self.storage.sync_all().unwrap();
+    // -- 分配新page, 返回pin住的空page
+    pub fn new_page(&self) -> Result<WritePageGuard<'_>, QcBupoError> {
+        return self.new_page_in(None, None);
+    }
+
+    // -- 按访问策略分配新page, 用于批量写入
+    pub fn new_page_with(&self, strategy: &mut QcAccessStrategy) -> Result<WritePageGuard<'_>, QcBupoError> {
+        return self.new_page_in(Some(strategy), None);
+    }
+
+    // -- 新page的id满足 page_id % num == rem, 由并行pool指定所在实例
+    pub(crate) fn new_page_striped(&self, num: u64, rem: u64) -> Result<WritePageGuard<'_>, QcBupoError> {
+        return self.new_page_in(None, Some((num, rem)));
+    }
 
-                Ok(())
+    fn new_page_in(&self, strategy: Option<&mut QcAccessStrategy>, stripe: Option<(u64, u64)>) -> Result<WritePageGuard<'_>, QcBupoError> {
+        let inner = &*self.inner;
+        let (fid, page_id) = {
+            let _miss = inner.miss.lock().unwrap();
+
+            // -- 先拿到frame, 避免pool满时浪费page id
+            let mut strategy = strategy;
+            let npgid = inner.take_frame(strategy.as_deref_mut())?;
+            let allocated = match stripe {
+                Some((num, rem)) => inner.storage.allocate_page_in(num, rem),
+                None => inner.storage.allocate_page(),
             };
-        } else {
-            return Ok(());
+            let page_id = match allocated {
+                Ok(pid) => pid,
+                Err(e) => {
+                    inner.frame_bits.clear(npgid);
+                    return Err(e.into());
+                }
+            };
+            if let Some(st) = strategy {
+                st.record(npgid, page_id);
+            }
+
+            let pg = QcPager::new();
+            pg.op_dirty();
+            *(inner.frame[npgid].write().unwrap()) = pg;
+            inner.install_frame(npgid, page_id, false);
+
+            (npgid, page_id)
+        };
+
+        return Ok(WritePageGuard::new(self, page_id, fid, inner.frame[fid].write().unwrap()));
+    }
+
+    // -- 删除page, page id回到free list
+    pub fn delete_page(&self, page_id: PageId) -> Result<(), QcBupoError> {
+        if page_id == HEADER_PAGE_ID || !page_id.is_valid() {
+            return Err(QcBupoError::InvalidPage);
+        }
+
+        let inner = &*self.inner;
+        let _miss = inner.miss.lock().unwrap();
+
+        // -- 未分配或已释放的page不能删除, 否则同一个page会被分配给两个使用者
+        if !inner.storage.is_allocated(page_id) {
+            return Err(QcBupoError::InvalidPage);
+        }
+        let mut shard = inner.table.shard(page_id);
+        if shard.get(&page_id).is_some_and(|pgi| pgi.ref_num > 0) {
+            return Err(QcBupoError::PagePinned);
+        }
+
+        // -- 释放前先记日志并落盘, 恢复时不会把之前的修改重做到空闲page上
+        if let Some(log) = &inner.log {
+            let lsn = log.append(INVALID_TXN, INVALID_LSN, QcLogBody::Free { page_id })?;
+            log.flush_to(lsn)?;
+        }
+
+        if let Some(pgi) = shard.get(&page_id) {
+            let fid = pgi.frame_id;
+            shard.remove(&page_id);
+            inner.replacer.lock().unwrap().remove(fid);
+            inner.frame_page[fid].store(PageId::INVALID.0, Ordering::Release);
+            inner.frame[fid].write().unwrap().op_clear();
+            inner.drop_dirty(fid);
+            inner.frame_bits.clear(fid);
+        }
+        drop(shard);
+
+        inner.storage.deallocate_page(page_id)?;
+        return Ok(());
+    }
+
+    // -- unpin, 引用归零后可被淘汰
+    pub fn unpin_page(&self, page_id: PageId) -> Option<()> {
+        return self.inner.unpin_page(page_id);
+    }
+
+    // -- 写回单个page, 只写dirty的page; pin住的page也可以刷
+    pub fn flush_page(&self, page_id: PageId) -> Result<(), QcBupoError> {
+        let inner = &*self.inner;
+        if inner.write_back(&[page_id])? > 0 && inner.sync != QcSyncPolicy::Never {
+            inner.storage.sync()?;
+        }
+
+        return Ok(());
+    }
+
+    // -- 写回所有dirty的page, 按page id分批提交, 最后统一sync一次
+    //      持读latch拷贝page, 同一线程持有读guard时也可以调用; 持有写guard时会等待它释放
+    pub fn flush_all(&self) -> Result<(), QcBupoError> {
+        let inner = &*self.inner;
+        if self.write_all()? > 0 && inner.sync != QcSyncPolicy::Never {
+            inner.storage.sync()?;
+        }
+
+        return Ok(());
+    }
+
+    // -- 写回所有dirty的page但不sync, 返回写了的page数
+    pub(crate) fn write_all(&self) -> Result<usize, QcBupoError> {
+        let inner = &*self.inner;
+
+        let mut pids: Vec<PageId> = Vec::new();
+        for shard in inner.table.shards() {
+            pids.extend(shard.keys());
+        }
+        pids.sort();
+
+        let mut written = 0;
+        for chunk in pids.chunks(inner.batch_size()) {
+            written += inner.write_back(chunk)?;
+        }
+
+        return Ok(written);
+    }
+
+    // -- 预读提示: 把range中不在pool里的page读入空闲或可淘汰的frame, 不pin住
+    //      最多占用一半的frame, 返回读入的page数; range中有未分配的page时返回InvalidPage
+    pub fn prefetch(&self, range: Range<PageId>) -> Result<usize, QcBupoError> {
+        let inner = &*self.inner;
+        let count = range.end.0.saturating_sub(range.start.0) as usize;
+        if count == 0 {
+            return Ok(0);
         }
+        if (range.start.0..range.end.0).any(|raw| !inner.storage.is_allocated(PageId(raw))) {
+            return Err(QcBupoError::InvalidPage);
+        }
+
+        return inner.prefetch(range.start, count);
+    }
+
+    // -- 预写日志, 没有配置时为None
+    pub fn log_manager(&self) -> Option<&Arc<QcLogManager>> {
+        self.inner.log.as_ref()
+    }
+
+    // -- 是否启用了后台刷脏线程
+    pub fn has_cleaner(&self) -> bool {
+        self.cleaner.is_some()
+    }
+
+    // -- 是否启用了后台预读线程
+    pub fn has_read_ahead(&self) -> bool {
+        self.reader.is_some()
+    }
+
+    // -- write guard 释放或unpin时记录的dirty frame数
+    pub fn dirty_count(&self) -> usize {
+        self.inner.dirty_num.load(Ordering::Relaxed)
     }
 
     pub fn report(&self) {
@@ -101,18 +350,588 @@ impl QcBuffpool {
         // for kp in self.frame.iter().enumerate() {
         // }
         println!("---frame bits---");
-        self.frame_bits.report();
+        self.inner.frame_bits.report();
         println!("---table---");
-        for (ti, tb) in self.table.iter() {
-            println!("\t{ti} info: ");
-            println!("\t{:?}", tb);
+        for shard in self.inner.table.shards() {
+            for (ti, tb) in shard.iter() {
+                println!("\t{ti} info: ");
+                println!("\t{:?}", tb);
+            }
         }
         println!("---frame---");
         print!("\t");
-        for (ti, kb) in self.frame.iter().enumerate() {
+        for (ti, kb) in self.inner.frame.iter().enumerate() {
             print!("{}: {} {} -> ", ti, Arc::strong_count(kb), Arc::weak_count(kb));
         }
         println!("|");
     }
 
+    pub(crate) fn mark_dirty(&self, frame_id: FrameId) {
+        self.inner.mark_dirty(frame_id);
+    }
+
+    pub(crate) fn begin_write(&self, frame_id: FrameId) -> bool {
+        self.inner.begin_write(frame_id)
+    }
+
+    pub(crate) fn cancel_write(&self, frame_id: FrameId) {
+        self.inner.cancel_write(frame_id);
+    }
+}
+
+impl QcPoolInner {
+    pub(crate) fn cleaner_signal(&self) -> &QcCleanerSignal {
+        &self.signal
+    }
+
+    pub(crate) fn read_ahead_signal(&self) -> &QcCleanerSignal {
+        &self.ahead_signal
+    }
+
+    pub(crate) fn take_read_ahead(&self) -> Option<PageId> {
+        self.ahead_reqs.lock().unwrap().pop_front()
+    }
+
+    pub(crate) fn read_ahead_window(&self, first: PageId) -> Result<usize, QcBupoError> {
+        self.prefetch(first, self.read_ahead)
+    }
+
+    pub(crate) fn log(&self) -> Option<&Arc<QcLogManager>> {
+        self.log.as_ref()
+    }
+
+    pub(crate) fn storage(&self) -> &Arc<dyn DiskManager> {
+        &self.storage
+    }
+
+    // -- 按page id顺序写回未pin的dirty page
+    pub(crate) fn clean_round(&self) -> Result<usize, QcBupoError> {
+        let mut cands: Vec<PageId> = Vec::new();
+        for shard in self.table.shards() {
+            cands.extend(shard
+                .iter()
+                .filter(|(_, pgi)| pgi.ref_num == 0)
+                .map(|(&pid, _)| pid));
+        }
+        cands.sort();
+
+        // -- 期间可能已被淘汰, write_back会跳过
+        let mut written = 0;
+        for chunk in cands.chunks(self.batch_size()) {
+            written += self.write_back(chunk)?;
+        }
+
+        if written > 0 && self.sync != QcSyncPolicy::Never {
+            self.storage.sync()?;
+        }
+
+        return Ok(written);
+    }
+
+    // -- 一次批量写回最多pin住的page数, 给其它线程留出frame
+    fn batch_size(&self) -> usize {
+        (self.frame.len() / 4).clamp(1, 32)
+    }
+
+    fn unpin_page(&self, page_id: PageId) -> Option<()> {
+        let mut shard = self.table.shard(page_id);
+
+        let Some(pgi) = shard.get_mut(&page_id) else {
+            return None;
+        };
+
+        if pgi.ref_num <= 0 {
+            return None;
+        }
+
+        // -- 经Weak句柄修改的page在unpin时计入dirty数; latch被占用时由持有者释放后负责
+        if let Ok(pg) = self.frame[pgi.frame_id].try_read() {
+            if pg.is_dirty() {
+                self.mark_dirty(pgi.frame_id);
+            }
+        }
+
+        pgi.ref_num -= 1;
+        if pgi.ref_num == 0 {
+            self.replacer.lock().unwrap().set_evictable(pgi.frame_id, true);
+        }
+
+        return Some(());
+    }
+
+    fn mark_dirty(&self, frame_id: FrameId) {
+        if self.dirty_hint[frame_id].swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let num = self.dirty_num.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(mark) = self.dirty_watermark {
+            if num >= mark {
+                self.signal.kick();
+            }
+        }
+    }
+
+    fn clear_dirty(&self, frame_id: FrameId) {
+        if self.dirty_hint[frame_id].swap(false, Ordering::Relaxed) {
+            self.dirty_num.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    // -- frame中的page已写回或被丢弃, 不再属于脏页表
+    fn drop_dirty(&self, frame_id: FrameId) {
+        self.clear_dirty(frame_id);
+        self.rec_lsn[frame_id].store(INVALID_LSN, Ordering::Release);
+    }
+
+    // -- 取得写latch后调用: 干净的frame记下当前日志末尾, 之后对它的修改LSN都不小于该值
+    //      返回是否由本次设置
+    pub(crate) fn begin_write(&self, frame_id: FrameId) -> bool {
+        let Some(log) = &self.log else {
+            return false;
+        };
+
+        return self.rec_lsn[frame_id]
+            .compare_exchange(INVALID_LSN, log.next_lsn(), Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+    }
+
+    // -- 仍持有写latch, 本次没有修改page时撤销begin_write的设置
+    pub(crate) fn cancel_write(&self, frame_id: FrameId) {
+        self.rec_lsn[frame_id].store(INVALID_LSN, Ordering::Release);
+    }
+
+    // -- fuzzy: 不加锁逐个读取, 被淘汰的page已先写回
+    pub(crate) fn dirty_page_table(&self) -> Vec<(PageId, Lsn)> {
+        let mut dpt = Vec::new();
+        for fid in 0..self.frame.len() {
+            let rec_lsn = self.rec_lsn[fid].load(Ordering::Acquire);
+            let pid = PageId(self.frame_page[fid].load(Ordering::Acquire));
+            if rec_lsn != INVALID_LSN && pid.is_valid() {
+                dpt.push((pid, rec_lsn));
+            }
+        }
+
+        return dpt;
+    }
+
+    // -- pin住page, 不在pool中则从磁盘读入; 发现顺序访问时顺带预读
+    fn pin_page(&self, page_id: PageId) -> Result<FrameId, QcBupoError> {
+        return self.pin_page_with(page_id, None);
+    }
+
+    // -- 使用访问策略时不预读, 预读的page会绕过ring
+    fn pin_page_with(&self, page_id: PageId, strategy: Option<&mut QcAccessStrategy>) -> Result<FrameId, QcBupoError> {
+        if page_id == HEADER_PAGE_ID || !page_id.is_valid() {
+            return Err(QcBupoError::InvalidPage);
+        }
+
+        let with_strategy = strategy.is_some();
+        let fid = self.pin_or_load(page_id, strategy)?;
+        if !with_strategy {
+            self.track_access(page_id);
+        }
+
+        return Ok(fid);
+    }
+
+    // -- 同一page的并发未命中只读一次盘, 其余线程等待读入完成
+    fn pin_or_load(&self, page_id: PageId, mut strategy: Option<&mut QcAccessStrategy>) -> Result<FrameId, QcBupoError> {
+        loop {
+            {
+                let mut shard = self.table.shard(page_id);
+                if let Some(fid) = self.pin_hit(&mut shard, page_id, true) {
+                    return Ok(fid);
+                }
+
+                // -- 正在被其它线程读入, 完成(或失败)后重新查找
+                if shard.contains_key(&page_id) {
+                    drop(self.table.wait_loaded(shard, page_id));
+                    continue;
+                }
+            }
+
+            // -- 持miss锁分配frame并登记为loading, 读盘时不持锁
+            let fid = {
+                let _miss = self.miss.lock().unwrap();
+                if self.table.shard(page_id).contains_key(&page_id) {
+                    continue;
+                }
+
+                // -- 未分配或已释放的page不能读入, 否则之后new_page分到同一个id时page表会冲突
+                if !self.storage.is_allocated(page_id) {
+                    return Err(QcBupoError::InvalidPage);
+                }
+
+                let fid = self.take_frame(strategy.as_deref_mut())?;
+                if let Some(st) = strategy {
+                    st.record(fid, page_id);
+                }
+                self.install_frame(fid, page_id, true);
+                fid
+            };
+
+            return self.load_frame(page_id, fid);
+        }
+    }
+
+    // -- 读入loading状态的frame, 校验和不对时不装入
+    fn load_frame(&self, page_id: PageId, frame_id: FrameId) -> Result<FrameId, QcBupoError> {
+        let mut tmp_pg = QcPager::new();
+        if let Err(e) = self.storage.read_page(page_id, tmp_pg.mut_buffer()) {
+            self.finish_load(page_id, frame_id, None);
+            return Err(e.into());
+        }
+
+        if !tmp_pg.checksum_ok() {
+            self.finish_load(page_id, frame_id, None);
+            return Err(QcBupoError::Corruption(page_id));
+        }
+
+        self.finish_load(page_id, frame_id, Some(tmp_pg));
+        return Ok(frame_id);
+    }
+
+    // -- 结束loading: 读到page则装入frame, 否则撤销登记; 都会唤醒等待的线程
+    fn finish_load(&self, page_id: PageId, frame_id: FrameId, pg: Option<QcPager>) {
+        let Some(mut pg) = pg else {
+            let mut shard = self.table.shard(page_id);
+            shard.remove(&page_id);
+            self.replacer.lock().unwrap().remove(frame_id);
+            self.frame_page[frame_id].store(PageId::INVALID.0, Ordering::Release);
+            self.frame_bits.clear(frame_id);
+            drop(shard);
+
+            self.table.notify_loaded(page_id);
+            return;
+        };
+
+        // -- 带LSN的page即使没有slot也保留, 否则会丢掉日志覆盖的修改
+        if !pg.is_valiable() && pg.page_lsn() == INVALID_LSN {
+            pg = QcPager::new();
+        }
+        pg.op_clear();
+        *(self.frame[frame_id].write().unwrap()) = pg;
+
+        self.table.shard(page_id).get_mut(&page_id).unwrap().loading = false;
+        self.table.notify_loaded(page_id);
+    }
+
+    // -- 同时跟踪的顺序流个数, 多个线程各自扫描时互不打断
+    const SEQ_STREAMS: usize = 8;
+    // -- 排队的预读请求上限, 预读线程跟不上时丢弃新的请求
+    const AHEAD_QUEUE: usize = 16;
+
+    // -- 某个流连续访问SEQ_TRIGGER次相邻page后, 下一个page不在pool中时登记预读一个窗口
+    //      读盘由预读线程完成, 不阻塞当前的fetch
+    fn track_access(&self, page_id: PageId) {
+        const SEQ_TRIGGER: usize = 2;
+
+        if self.read_ahead == 0 {
+            return;
+        }
+
+        let run = {
+            let mut streams = self.seq_streams.lock().unwrap();
+            if let Some(st) = streams.iter_mut().find(|st| st.0.wrapping_add(1) == page_id.0) {
+                // -- 接在某个流之后
+                st.0 = page_id.0;
+                st.1 += 1;
+                st.1
+            } else {
+                // -- 重复访问流的当前page不算打断, 否则替换连续次数最少的流
+                if !streams.iter().any(|st| st.0 == page_id.0) {
+                    let st = streams.iter_mut().min_by_key(|st| st.1).unwrap();
+                    *st = (page_id.0, 0);
+                }
+                0
+            }
+        };
+        if run < SEQ_TRIGGER {
+            return;
+        }
+
+        // -- 离已读入部分的末尾不足半个窗口时就预读下一个窗口, 不等到下一个page缺失
+        let half = (self.read_ahead / 2).max(1) as u64;
+        let Some(next) = (1..=half)
+            .map(|d| PageId(page_id.0.saturating_add(d)))
+            .find(|pid| !self.table.shard(*pid).contains_key(pid) && self.storage.is_allocated(*pid)) else {
+            return;
+        };
+
+        let mut reqs = self.ahead_reqs.lock().unwrap();
+        if reqs.len() >= Self::AHEAD_QUEUE || reqs.contains(&next) {
+            return;
+        }
+        reqs.push_back(next);
+        drop(reqs);
+        self.ahead_signal.kick();
+    }
+
+    fn prefetch(&self, first: PageId, count: usize) -> Result<usize, QcBupoError> {
+        let count = count.min((self.frame.len() / 2).max(1));
+
+        let mut loads: Vec<(PageId, FrameId)> = Vec::new();
+        {
+            let _miss = self.miss.lock().unwrap();
+            for raw in first.0..first.0.saturating_add(count as u64) {
+                let pid = PageId(raw);
+                if pid == HEADER_PAGE_ID || !pid.is_valid() || self.table.shard(pid).contains_key(&pid) {
+                    continue;
+                }
+                // -- 预读窗口可能越过文件末尾或碰到已释放的page
+                if !self.storage.is_allocated(pid) {
+                    continue;
+                }
+
+                // -- 没有可用frame时只读已拿到的部分
+                let Ok(fid) = self.enable_frame_id() else {
+                    break;
+                };
+                self.install_frame(fid, pid, true);
+                loads.push((pid, fid));
+            }
+        }
+
+        // -- 读盘前提示内核预读整个区间
+        if let (Some(&(lo, _)), Some(&(hi, _))) = (loads.first(), loads.last()) {
+            self.storage.advise(lo, (hi.0 - lo.0 + 1) as usize);
+        }
+
+        let mut pgs: Vec<QcPager> = loads.iter().map(|_| QcPager::new()).collect();
+        let result = {
+            let mut reqs: Vec<(PageId, &mut [u8])> = loads.iter()
+                .zip(pgs.iter_mut())
+                .map(|(&(pid, _), pg)| (pid, pg.mut_buffer()))
+                .collect();
+            self.storage.read_pages(&mut reqs)
+        };
+
+        // -- 校验失败的page不装入, 留给之后的fetch报告
+        let ok = result.is_ok();
+        let mut loaded = 0;
+        for (&(pid, fid), pg) in loads.iter().zip(pgs) {
+            let pg = Some(pg).filter(|pg| ok && pg.checksum_ok());
+            let hit = pg.is_some();
+            self.finish_load(pid, fid, pg);
+            if hit {
+                self.unpin_page(pid);
+                loaded += 1;
+            }
+        }
+        result?;
+
+        return Ok(loaded);
+    }
+
+    // -- 已在pool中则pin住; 正在读入的不算命中
+    //      access: 是否计入replacer的访问记录, 内部刷盘时不计
+    fn pin_hit(&self, shard: &mut MutexGuard<'_, QcShard>, page_id: PageId, access: bool) -> Option<FrameId> {
+        let Some(pgi) = shard.get_mut(&page_id).filter(|pgi| !pgi.loading) else {
+            return None;
+        };
+
+        pgi.ref_num += 1;
+        let mut replacer = self.replacer.lock().unwrap();
+        if access {
+            replacer.record_access(pgi.frame_id, page_id);
+        }
+        replacer.set_evictable(pgi.frame_id, false);
+
+        return Some(pgi.frame_id);
+    }
+
+    // -- 批量写回dirty page: 先pin住, 持读latch拷贝并清除dirty, 校验和填在拷贝中, 一次提交给存储层
+    //      持有读guard的线程也可以调用; 不在pool中或正在读入的page跳过; 写失败则重新标记dirty; 返回写了的page数
+    fn write_back(&self, pids: &[PageId]) -> Result<usize, QcBupoError> {
+        let mut batch: Vec<(PageId, FrameId, Box<[u8]>)> = Vec::new();
+        let mut max_lsn = 0;
+        for &pid in pids {
+            let Some(fid) = self.pin_hit(&mut self.table.shard(pid), pid, false) else {
+                continue;
+            };
+
+            let pg = self.frame[fid].read().unwrap();
+            if !pg.is_dirty() {
+                drop(pg);
+                self.unpin_page(pid);
+                continue;
+            }
+
+            max_lsn = max_lsn.max(pg.page_lsn());
+            let mut buf: Box<[u8]> = pg.buffer().into();
+            checksum::stamp_page(&mut buf);
+            batch.push((pid, fid, buf));
+            pg.op_clear();
+            drop(pg);
+            self.clear_dirty(fid);
+        }
+
+        if batch.is_empty() {
+            return Ok(0);
+        }
+
+        let reqs: Vec<(PageId, &[u8])> = batch.iter().map(|(pid, _, buf)| (*pid, &buf[..])).collect();
+        let result = self.force_log(max_lsn)
+            .and_then(|_| self.storage.write_pages(&reqs));
+
+        for &(pid, fid, _) in batch.iter() {
+            if result.is_err() {
+                self.frame[fid].read().unwrap().op_dirty();
+                self.mark_dirty(fid);
+            } else {
+                // -- 写回期间又被修改的保留原rec lsn; 持有latch, 避免与begin_write交错
+                let pg = self.frame[fid].read().unwrap();
+                if !pg.is_dirty() {
+                    self.rec_lsn[fid].store(INVALID_LSN, Ordering::Release);
+                }
+            }
+            self.unpin_page(pid);
+        }
+        result?;
+
+        return Ok(batch.len());
+    }
+
+    // -- frame装入page后登记, pin计数为1
+    fn install_frame(&self, frame_id: FrameId, page_id: PageId, loading: bool) {
+        self.frame_page[frame_id].store(page_id.0, Ordering::Release);
+
+        let mut shard = self.table.shard(page_id);
+        let item = if loading {
+            QcBuffItem::loading(frame_id)
+        } else {
+            QcBuffItem::new(frame_id, 1)
+        };
+        shard.insert(page_id, item);
+
+        let mut replacer = self.replacer.lock().unwrap();
+        replacer.record_access(frame_id, page_id);
+        replacer.set_evictable(frame_id, false);
+    }
+
+    // -- 可用frame: 先找空位, 再淘汰未pin的page; 需持有miss锁
+    //      返回的frame不在table中, 其bit保持占用
+    fn enable_frame_id(&self) -> Result<FrameId, QcBupoError> {
+        // 存在空位
+        if let Some(frame_id) = self.frame_bits.issue_set() {
+            return Ok(frame_id);
+        }
+
+        loop {
+            let Some(vfid) = self.replacer.lock().unwrap().evict() else {
+                return Err(QcBupoError::NoFreeFrame);
+            };
+
+            let vpid = PageId(self.frame_page[vfid].load(Ordering::Acquire));
+            if !vpid.is_valid() {
+                panic!("conflict the pager replacer");
+            }
+
+            let mut shard = self.table.shard(vpid);
+
+            // -- evict 与加分片锁之间可能被重新pin, 放回replacer后换下一个
+            let pgi = shard.get(&vpid).expect("conflict the pager table");
+            if pgi.frame_id != vfid {
+                panic!("conflict the pager table");
+            }
+            let mut replacer = self.replacer.lock().unwrap();
+            if pgi.ref_num > 0 {
+                replacer.record_access(vfid, vpid);
+                replacer.set_evictable(vfid, false);
+                continue;
+            }
+            // -- 期间pin又unpin过时可能已被重新加入
+            replacer.remove(vfid);
+            drop(replacer);
+
+            let mut vpg = self.frame[vfid].write().unwrap();
+            if vpg.is_dirty() {
+                // -- 写回失败则放回replacer, page仍留在pool中
+                if let Err(e) = self.write_victim(vpid, &mut vpg) {
+                    drop(vpg);
+                    let mut replacer = self.replacer.lock().unwrap();
+                    replacer.record_access(vfid, vpid);
+                    replacer.set_evictable(vfid, true);
+                    return Err(e.into());
+                }
+                vpg.op_clear();
+            }
+            drop(vpg);
+            self.drop_dirty(vfid);
+
+            shard.remove(&vpid);
+            self.frame_page[vfid].store(PageId::INVALID.0, Ordering::Release);
+
+            return Ok(vfid);
+        }
+    }
+
+    // -- 写回要被换出的page, Always策略下立即sync
+    fn write_victim(&self, page_id: PageId, pg: &mut QcPager) -> io::Result<()> {
+        self.force_log(pg.page_lsn())?;
+        pg.set_checksum();
+        self.storage.write_page(page_id, pg.buffer())?;
+        if self.sync == QcSyncPolicy::Always {
+            self.storage.sync()?;
+        }
+
+        return Ok(());
+    }
+
+    // -- WAL: page写盘前日志需落盘到它的LSN, 失败则不能写
+    fn force_log(&self, lsn: Lsn) -> io::Result<()> {
+        match &self.log {
+            Some(log) if lsn != INVALID_LSN => log.flush_to(lsn),
+            _ => Ok(()),
+        }
+    }
+
+    // -- 未命中时取frame: 有策略则先复用ring当前位置的frame, 不行再从pool中取
+    //      需持有miss锁
+    fn take_frame(&self, strategy: Option<&mut QcAccessStrategy>) -> Result<FrameId, QcBupoError> {
+        let Some(st) = strategy else {
+            return self.enable_frame_id();
+        };
+
+        if let Some((fid, pid)) = st.advance() {
+            if self.reclaim_frame(fid, pid, st.kind)? {
+                return Ok(fid);
+            }
+        }
+
+        return self.enable_frame_id();
+    }
+
+    // -- 收回ring中记录的frame: 需仍装着记录的page且未被pin
+    //      BulkRead遇到dirty的page不收回, BulkWrite先写回
+    fn reclaim_frame(&self, frame_id: FrameId, page_id: PageId, kind: QcStrategyKind) -> Result<bool, QcBupoError> {
+        if self.frame_page[frame_id].load(Ordering::Acquire) != page_id.0 {
+            return Ok(false);
+        }
+
+        let mut shard = self.table.shard(page_id);
+        match shard.get(&page_id) {
+            Some(pgi) if pgi.frame_id == frame_id && pgi.ref_num == 0 && !pgi.loading => {}
+            _ => return Ok(false),
+        }
+
+        let mut pg = self.frame[frame_id].write().unwrap();
+        if pg.is_dirty() {
+            if kind == QcStrategyKind::BulkRead {
+                return Ok(false);
+            }
+
+            self.write_victim(page_id, &mut pg)?;
+            pg.op_clear();
+        }
+        drop(pg);
+        self.drop_dirty(frame_id);
+
+        self.replacer.lock().unwrap().remove(frame_id);
+        shard.remove(&page_id);
+        self.frame_page[frame_id].store(PageId::INVALID.0, Ordering::Release);
+
+        return Ok(true);
+    }
 }
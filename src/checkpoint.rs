@@ -0,0 +1,106 @@
+use std::{sync::Arc, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use crate::{buffpool::QcPoolInner, cleaner::QcCleanerSignal, error::QcBupoError, wal::{Lsn, QcLogBody, INVALID_LSN, INVALID_TXN}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QcCheckpointConfig {
+    // -- 距上次checkpoint超过该时间则做一次
+    pub interval: Option<Duration>,
+    // -- 距上次checkpoint新写的日志超过该字节数则做一次
+    pub log_bytes: Option<u64>,
+}
+
+impl Default for QcCheckpointConfig {
+    fn default() -> Self {
+        QcCheckpointConfig {
+            interval: Some(Duration::from_secs(30)),
+            log_bytes: Some(16 * 1024 * 1024),
+        }
+    }
+}
+
+// -- fuzzy checkpoint: 写begin后取各实例的脏页表与活跃事务表写入end, 期间不阻塞fetch
+//      日志与数据落盘后才把begin LSN记到header page, 返回该LSN; 没有日志时返回INVALID_LSN
+pub(crate) fn checkpoint(pools: &[Arc<QcPoolInner>]) -> Result<Lsn, QcBupoError> {
+    let Some(inner) = pools.first() else {
+        return Ok(INVALID_LSN);
+    };
+    let Some(log) = inner.log() else {
+        return Ok(INVALID_LSN);
+    };
+
+    let begin = log.append(INVALID_TXN, INVALID_LSN, QcLogBody::CheckpointBegin)?;
+
+    let mut dpt = Vec::new();
+    for pool in pools.iter() {
+        dpt.extend(pool.dirty_page_table());
+    }
+    dpt.sort_unstable();
+    let att = log.active_txns();
+
+    let end = log.append(INVALID_TXN, INVALID_LSN, QcLogBody::CheckpointEnd { dpt, att })?;
+    log.flush_to(end)?;
+
+    // -- 已写回而不在脏页表中的page也要落盘
+    inner.storage().sync()?;
+    inner.storage().set_checkpoint_lsn(begin)?;
+
+    return Ok(begin);
+}
+
+// -- 后台checkpoint线程, 随pool一起销毁
+pub(crate) struct QcCheckpointer {
+    signal: Arc<QcCleanerSignal>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl QcCheckpointer {
+    // -- 检查日志量的间隔
+    const POLL: Duration = Duration::from_millis(50);
+
+    pub(crate) fn spawn(pools: Vec<Arc<QcPoolInner>>, config: QcCheckpointConfig) -> Self {
+        let signal = Arc::new(QcCleanerSignal::default());
+        let stop = Arc::clone(&signal);
+        let poll = config.interval.map_or(Self::POLL, |iv| iv.min(Self::POLL));
+
+        let handle = thread::Builder::new()
+            .name("qc-checkpointer".to_string())
+            .spawn(move || {
+                let Some(log) = pools[0].log().cloned() else {
+                    return;
+                };
+
+                // -- 日志量从上一个checkpoint算起, 包括打开之前写的
+                let mut last_time = Instant::now();
+                let mut last_lsn = pools[0].storage().checkpoint_lsn().unwrap_or(INVALID_LSN);
+                while !stop.wait(poll) {
+                    let by_time = config.interval.is_some_and(|iv| last_time.elapsed() >= iv);
+                    let by_log = config.log_bytes.is_some_and(|lb| log.next_lsn().saturating_sub(last_lsn) >= lb);
+                    if !by_time && !by_log {
+                        continue;
+                    }
+
+                    // -- 失败则下一轮再试, 恢复从上一个成功的checkpoint开始
+                    if let Ok(lsn) = checkpoint(&pools) {
+                        last_time = Instant::now();
+                        last_lsn = lsn;
+                    }
+                }
+            })
+            .expect("spawn checkpointer");
+
+        QcCheckpointer {
+            signal,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for QcCheckpointer {
+    fn drop(&mut self) {
+        self.signal.stop();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
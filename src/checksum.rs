@@ -0,0 +1,50 @@
+use crate::page::PAGE_SIZE;
+
+// -- CRC32C (Castagnoli), 查表实现
+const POLY: u32 = 0x82F6_3B78;
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLY } else { crc >> 1 };
+            k += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+
+    return table;
+}
+
+static TABLE: [u32; 256] = make_table();
+
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &b in data {
+        crc = TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    return !crc;
+}
+
+// -- page的[0~3]存放其余部分的校验和
+pub fn stamp_page(buf: &mut [u8]) {
+    debug_assert_eq!(buf.len(), PAGE_SIZE);
+
+    let crc = crc32c(&buf[4..]);
+    buf[0..4].copy_from_slice(&crc.to_be_bytes());
+}
+
+// -- 从未写过的page全为0, 视为有效
+pub fn verify_page(buf: &[u8]) -> bool {
+    let stored = u32::from_be_bytes(buf[0..4].try_into().unwrap());
+    if stored == crc32c(&buf[4..]) {
+        return true;
+    }
+
+    return buf.iter().all(|&b| b == 0);
+}
@@ -0,0 +1,91 @@
+use std::{sync::{Arc, Condvar, Mutex}, thread::{self, JoinHandle}, time::Duration};
+
+use crate::buffpool::QcPoolInner;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QcCleanerConfig {
+    // -- 定期唤醒的间隔
+    pub interval: Duration,
+    // -- dirty frame 占比超过该值时提前唤醒, 取值 (0, 1]
+    pub dirty_watermark: f64,
+}
+
+impl Default for QcCleanerConfig {
+    fn default() -> Self {
+        QcCleanerConfig {
+            interval: Duration::from_millis(100),
+            dirty_watermark: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QcCleanerFlag {
+    stop: bool,
+    kicked: bool,
+}
+
+// -- pool 与 cleaner 线程之间的唤醒信号
+#[derive(Debug, Default)]
+pub(crate) struct QcCleanerSignal {
+    flag: Mutex<QcCleanerFlag>,
+    cond: Condvar,
+}
+
+impl QcCleanerSignal {
+    pub(crate) fn kick(&self) {
+        self.flag.lock().unwrap().kicked = true;
+        self.cond.notify_one();
+    }
+
+    pub(crate) fn stop(&self) {
+        self.flag.lock().unwrap().stop = true;
+        self.cond.notify_one();
+    }
+
+    // -- 等到超时或被唤醒, 返回是否需要退出
+    pub(crate) fn wait(&self, interval: Duration) -> bool {
+        let flag = self.flag.lock().unwrap();
+        let (mut flag, _) = self.cond
+            .wait_timeout_while(flag, interval, |fg| !fg.stop && !fg.kicked)
+            .unwrap();
+
+        flag.kicked = false;
+        return flag.stop;
+    }
+}
+
+// -- 后台刷脏线程, 随 QcBuffpool 一起销毁
+pub(crate) struct QcPageCleaner {
+    inner: Arc<QcPoolInner>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl QcPageCleaner {
+    pub(crate) fn spawn(inner: Arc<QcPoolInner>, config: QcCleanerConfig) -> Self {
+        let pool = Arc::clone(&inner);
+        let handle = thread::Builder::new()
+            .name("qc-page-cleaner".to_string())
+            .spawn(move || {
+                while !pool.cleaner_signal().wait(config.interval) {
+                    // -- 写失败的page仍是dirty, 下一轮或淘汰时再写
+                    let _ = pool.clean_round();
+                }
+            })
+            .expect("spawn page cleaner");
+
+        QcPageCleaner {
+            inner,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for QcPageCleaner {
+    fn drop(&mut self) {
+        self.inner.cleaner_signal().stop();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::bitmap::Qcbitmap;
+use crate::replacer::{FrameId, Replacer};
+use crate::trace::PageId;
+
+// -- CLOCK(second chance): 每个frame一个引用位
+//      指针扫过时引用位为1则清零跳过, 为0则淘汰
+#[derive(Debug)]
+pub struct QcClockReplacer {
+    capacity: usize,
+    hand: usize,
+    evictable_num: usize,
+    refbits: Qcbitmap,
+    evictable: Qcbitmap,
+}
+
+impl QcClockReplacer {
+    pub fn new(capacity: usize) -> Self {
+        QcClockReplacer {
+            capacity,
+            hand: 0,
+            evictable_num: 0,
+            refbits: Qcbitmap::new(capacity),
+            evictable: Qcbitmap::new(capacity),
+        }
+    }
+
+    pub fn report(&self) {
+        println!("hand: {}", self.hand);
+        self.refbits.report();
+        self.evictable.report();
+    }
+}
+
+impl Replacer for QcClockReplacer {
+    fn record_access(&mut self, frame_id: FrameId, _page_id: PageId) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        self.refbits.set(frame_id);
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        if self.evictable.get(frame_id) == evictable {
+            return;
+        }
+
+        if evictable {
+            self.evictable.set(frame_id);
+            self.evictable_num += 1;
+        } else {
+            self.evictable.clear(frame_id);
+            self.evictable_num -= 1;
+        }
+    }
+
+    fn evict(&mut self) -> Option<FrameId> {
+        if self.evictable_num == 0 {
+            return None;
+        }
+
+        // -- 最多两圈: 第一圈清引用位, 第二圈必有victim
+        for _ in 0..(2 * self.capacity) {
+            let fid = self.hand;
+            self.hand = (self.hand + 1) % self.capacity;
+
+            if !self.evictable.get(fid) {
+                continue;
+            }
+
+            if self.refbits.get(fid) {
+                self.refbits.clear(fid);
+                continue;
+            }
+
+            self.remove(fid);
+            return Some(fid);
+        }
+
+        return None;
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        if self.evictable.get(frame_id) {
+            self.evictable.clear(frame_id);
+            self.evictable_num -= 1;
+        }
+        self.refbits.clear(frame_id);
+    }
+
+    fn size(&self) -> usize {
+        return self.evictable_num;
+    }
+}
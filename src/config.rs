@@ -0,0 +1,247 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use crate::{arc::QcArcReplacer, buffpool::QcBuffpool, checkpoint::QcCheckpointConfig, cleaner::QcCleanerConfig, clock::QcClockReplacer, disk::{DiskManager, QcFileDisk, QcMemoryDisk}, double_write::QcDoubleWriteDisk, error::QcBupoError, lru_k::QcLruKReplacer, parallel::QcParallelBuffpool, replacer::Replacer, trace::QcTracer, two_queue::QcTwoQueueReplacer, wal::QcLogManager};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QcReplacerKind {
+    Lru,
+    LruK(usize),
+    Clock,
+    Arc,
+    TwoQueue { kin_ratio: f64, kout_ratio: f64 },
+}
+
+impl QcReplacerKind {
+    pub fn build(&self, size: usize) -> Box<dyn Replacer> {
+        match *self {
+            QcReplacerKind::Lru => Box::new(QcTracer::new(size)),
+            QcReplacerKind::LruK(k) => Box::new(QcLruKReplacer::new(size, k)),
+            QcReplacerKind::Clock => Box::new(QcClockReplacer::new(size)),
+            QcReplacerKind::Arc => Box::new(QcArcReplacer::new(size)),
+            QcReplacerKind::TwoQueue { kin_ratio, kout_ratio } => {
+                Box::new(QcTwoQueueReplacer::new(size, kin_ratio, kout_ratio))
+            }
+        }
+    }
+}
+
+// -- 写回后何时fsync
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QcSyncPolicy {
+    // -- 每次写回(包括淘汰)后都sync
+    Always,
+    // -- 只在显式flush时sync
+    Flush,
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QcStorage {
+    File(PathBuf),
+    // -- 同File, 读写经io_uring批量提交
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(PathBuf),
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub struct QcBuffpoolConfig {
+    pub(crate) storage: QcStorage,
+    pub(crate) file_pages: u64,
+    pub(crate) pool_size: usize,
+    pub(crate) replacer: QcReplacerKind,
+    pub(crate) sync: QcSyncPolicy,
+    pub(crate) cleaner: Option<QcCleanerConfig>,
+    pub(crate) read_ahead: usize,
+    pub(crate) double_write: bool,
+    pub(crate) wal: Option<PathBuf>,
+    pub(crate) checkpoint: Option<QcCheckpointConfig>,
+}
+
+impl QcBuffpoolConfig {
+    const DEFAULT_PATH: &'static str = "tmp_buffer.db";
+    const DEFAULT_FILE_PAGES: u64 = 16;
+    const DEFAULT_POOL_SIZE: usize = 8;
+
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        QcBuffpoolConfig {
+            storage: QcStorage::File(path.as_ref().to_path_buf()),
+            file_pages: Self::DEFAULT_FILE_PAGES,
+            pool_size: Self::DEFAULT_POOL_SIZE,
+            replacer: QcReplacerKind::Lru,
+            sync: QcSyncPolicy::Flush,
+            cleaner: None,
+            read_ahead: 0,
+            double_write: false,
+            wal: None,
+            checkpoint: None,
+        }
+    }
+
+    // -- 不落盘, 数据只在内存中
+    pub fn memory() -> Self {
+        QcBuffpoolConfig {
+            storage: QcStorage::Memory,
+            ..Self::default()
+        }
+    }
+
+    // -- 文件存储, page读写走io_uring
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn uring<T: AsRef<Path>>(path: T) -> Self {
+        QcBuffpoolConfig {
+            storage: QcStorage::Uring(path.as_ref().to_path_buf()),
+            ..Self::default()
+        }
+    }
+
+    // -- 数据文件的初始大小(page数), 已有文件不会被截断
+    pub fn file_pages(mut self, pages: u64) -> Self {
+        self.file_pages = pages;
+        self
+    }
+
+    // -- frame数
+    pub fn pool_size(mut self, size: usize) -> Self {
+        self.pool_size = size;
+        self
+    }
+
+    pub fn replacer(mut self, kind: QcReplacerKind) -> Self {
+        self.replacer = kind;
+        self
+    }
+
+    pub fn sync_policy(mut self, sync: QcSyncPolicy) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    // -- 启用后台刷脏线程
+    pub fn cleaner(mut self, cleaner: QcCleanerConfig) -> Self {
+        self.cleaner = Some(cleaner);
+        self
+    }
+
+    // -- 顺序访问时的预读窗口(page数), 0为关闭; 实际最多占用一半的frame
+    pub fn read_ahead(mut self, window: usize) -> Self {
+        self.read_ahead = window;
+        self
+    }
+
+    // -- 写page前先写<path>.dwb, 防止原地写入被撕裂; 只用于文件存储
+    pub fn double_write(mut self, on: bool) -> Self {
+        self.double_write = on;
+        self
+    }
+
+    // -- 预写日志文件; dirty page写回前日志必须已落盘到该page的LSN
+    //      打开时按日志做崩溃恢复
+    pub fn wal<T: AsRef<Path>>(mut self, path: T) -> Self {
+        self.wal = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // -- 后台定时或按日志量做checkpoint, 需配置日志
+    pub fn checkpoint(mut self, checkpoint: QcCheckpointConfig) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub fn build(self) -> Result<QcBuffpool, QcBupoError> {
+        self.validate(false)?;
+        let storage = self.open_storage()?;
+        let log = self.open_log()?;
+        let replacer = self.replacer.build(self.pool_size);
+        return QcBuffpool::open(&self, replacer, storage, log)?.recover_on_open(&self);
+    }
+
+    // -- 使用自定义的存储后端, 忽略storage配置
+    pub fn build_with_disk(self, storage: Box<dyn DiskManager>) -> Result<QcBuffpool, QcBupoError> {
+        self.validate(true)?;
+        let log = self.open_log()?;
+        let replacer = self.replacer.build(self.pool_size);
+        return QcBuffpool::open(&self, replacer, Arc::from(storage), log)?.recover_on_open(&self);
+    }
+
+    // -- 多个实例共享存储与日志, 每个实例pool_size个frame
+    pub fn build_parallel(self, instances: usize) -> Result<QcParallelBuffpool, QcBupoError> {
+        self.validate(false)?;
+        if instances == 0 {
+            return Err(QcBupoError::InvalidConfig("instance number must be positive"));
+        }
+        let storage = self.open_storage()?;
+        let log = self.open_log()?;
+        return QcParallelBuffpool::open(&self, instances, storage, log)?.recover_on_open(&self);
+    }
+
+    // -- 互相冲突或越界的配置在打开存储前报错, 而不是被忽略或在运行时panic
+    //      custom_disk: 使用自定义的存储后端
+    pub(crate) fn validate(&self, custom_disk: bool) -> Result<(), QcBupoError> {
+        if self.pool_size == 0 {
+            return Err(QcBupoError::InvalidConfig("pool size must be positive"));
+        }
+        if let Some(cc) = &self.checkpoint {
+            if self.wal.is_none() {
+                return Err(QcBupoError::InvalidConfig("checkpoint requires a write-ahead log"));
+            }
+            // -- 为0时每次轮询都会做checkpoint
+            if cc.interval.is_some_and(|iv| iv.is_zero()) || cc.log_bytes == Some(0) {
+                return Err(QcBupoError::InvalidConfig("checkpoint interval and log size must be positive"));
+            }
+        }
+        if self.double_write && (custom_disk || self.storage == QcStorage::Memory) {
+            return Err(QcBupoError::InvalidConfig("double write requires file storage"));
+        }
+        match self.replacer {
+            QcReplacerKind::LruK(0) => {
+                return Err(QcBupoError::InvalidConfig("lru-k needs k to be positive"));
+            }
+            QcReplacerKind::TwoQueue { kin_ratio, kout_ratio }
+                if !(0.0..=1.0).contains(&kin_ratio) || !(0.0..).contains(&kout_ratio) => {
+                return Err(QcBupoError::InvalidConfig("2q ratio out of range"));
+            }
+            _ => {}
+        }
+        if let Some(cc) = &self.cleaner {
+            if !(cc.dirty_watermark > 0.0 && cc.dirty_watermark <= 1.0) {
+                return Err(QcBupoError::InvalidConfig("dirty watermark out of range"));
+            }
+            if cc.interval.is_zero() {
+                return Err(QcBupoError::InvalidConfig("cleaner interval must be positive"));
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub(crate) fn open_log(&self) -> Result<Option<Arc<QcLogManager>>, QcBupoError> {
+        let Some(path) = &self.wal else {
+            return Ok(None);
+        };
+
+        return Ok(Some(Arc::new(QcLogManager::open(path)?)));
+    }
+
+    pub(crate) fn open_storage(&self) -> Result<Arc<dyn DiskManager>, QcBupoError> {
+        let (disk, path): (Box<dyn DiskManager>, &PathBuf) = match &self.storage {
+            QcStorage::File(path) => (Box::new(QcFileDisk::open(path, self.file_pages)?), path),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            QcStorage::Uring(path) => (Box::new(crate::uring::QcUringDisk::open(path, self.file_pages)?), path),
+            QcStorage::Memory => return Ok(Arc::new(QcMemoryDisk::new())),
+        };
+
+        if !self.double_write {
+            return Ok(Arc::from(disk));
+        }
+
+        let dwb_path = QcDoubleWriteDisk::dwb_path(path);
+        return Ok(Arc::new(QcDoubleWriteDisk::open(disk, dwb_path)?));
+    }
+}
+
+impl Default for QcBuffpoolConfig {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_PATH)
+    }
+}
@@ -0,0 +1,520 @@
+use std::{collections::{HashMap, HashSet}, fs::File, io, os::{fd::{AsRawFd, RawFd}, unix::fs::FileExt}, path::Path, sync::Mutex};
+
+use crate::{checksum, page::PAGE_SIZE, trace::PageId, wal::{Lsn, INVALID_LSN}};
+
+// -- page 0 固定为header page, 不会被分配
+pub const HEADER_PAGE_ID: PageId = PageId(0);
+
+// -- 数据文件格式版本, page布局(校验和, page LSN, free page)变化时加1
+//      没有版本号的旧文件按0处理
+pub const FORMAT_VERSION: u32 = 1;
+
+// -- 存储层: QcBuffpool 只通过它读写page
+pub trait DiskManager: Send + Sync {
+    // -- 超出已写范围的部分填0
+    fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> io::Result<()>;
+    fn write_page(&self, page_id: PageId, buf: &[u8]) -> io::Result<()>;
+    // -- 优先复用free list中的page
+    fn allocate_page(&self) -> io::Result<PageId>;
+    fn deallocate_page(&self, page_id: PageId) -> io::Result<()>;
+    fn sync(&self) -> io::Result<()>;
+
+    // -- 分配满足 page_id % num == rem 的page, 并行pool用它把新page放到指定实例
+    //      默认逐个分配, 不满足的按原顺序归还
+    fn allocate_page_in(&self, num: u64, rem: u64) -> io::Result<PageId> {
+        let mut skipped = Vec::new();
+        let result = loop {
+            match self.allocate_page() {
+                Ok(pid) if pid.0 % num == rem => break Ok(pid),
+                Ok(pid) => skipped.push(pid),
+                Err(e) => break Err(e),
+            }
+        };
+
+        for &pid in skipped.iter().rev() {
+            self.deallocate_page(pid)?;
+        }
+
+        return result;
+    }
+
+    // -- 已分配且没有被释放, 默认不检查
+    fn is_allocated(&self, _page_id: PageId) -> bool {
+        return true;
+    }
+
+    // -- 提示即将读取从first开始的count个page, 默认忽略
+    fn advise(&self, _first: PageId, _count: usize) {}
+
+    // -- 最近一次完成的checkpoint的begin LSN, 保存在header page中; 默认不保存
+    fn checkpoint_lsn(&self) -> io::Result<Lsn> {
+        return Ok(INVALID_LSN);
+    }
+
+    // -- 返回前需已落盘
+    fn set_checkpoint_lsn(&self, _lsn: Lsn) -> io::Result<()> {
+        return Ok(());
+    }
+
+    // -- 批量读写, 默认逐个执行; 异步后端可以一次提交
+    fn read_pages(&self, reqs: &mut [(PageId, &mut [u8])]) -> io::Result<()> {
+        for (pid, buf) in reqs.iter_mut() {
+            self.read_page(*pid, buf)?;
+        }
+
+        return Ok(());
+    }
+
+    fn write_pages(&self, reqs: &[(PageId, &[u8])]) -> io::Result<()> {
+        for (pid, buf) in reqs.iter() {
+            self.write_page(*pid, buf)?;
+        }
+
+        return Ok(());
+    }
+}
+
+fn to_page_id(raw: u64) -> io::Result<PageId> {
+    let pid = PageId(raw);
+    if !pid.is_valid() {
+        return Err(io::Error::other("page id exhausted"));
+    }
+
+    return Ok(pid);
+}
+
+// -- header page 布局:
+//      [0~3]: magic, [4~11]: next_page, [12~19]: free_head, [20~27]: checkpoint_lsn, [28~31]: 格式版本
+#[derive(Debug, Clone, Copy)]
+struct QcDiskHeader {
+    next_page: u64,
+    free_head: u64,
+    checkpoint_lsn: Lsn,
+    version: u32,
+}
+
+impl QcDiskHeader {
+    const MAGIC: &'static [u8; 4] = b"QCBP";
+
+    fn encode(&self) -> [u8; PAGE_SIZE] {
+        let mut buf = [0_u8; PAGE_SIZE];
+        buf[0..4].copy_from_slice(Self::MAGIC);
+        buf[4..12].copy_from_slice(&self.next_page.to_be_bytes());
+        buf[12..20].copy_from_slice(&self.free_head.to_be_bytes());
+        buf[20..28].copy_from_slice(&self.checkpoint_lsn.to_be_bytes());
+        buf[28..32].copy_from_slice(&self.version.to_be_bytes());
+
+        return buf;
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        if &buf[0..4] != Self::MAGIC {
+            return None;
+        }
+
+        return Some(QcDiskHeader {
+            next_page: u64::from_be_bytes(buf[4..12].try_into().unwrap()),
+            free_head: u64::from_be_bytes(buf[12..20].try_into().unwrap()),
+            checkpoint_lsn: u64::from_be_bytes(buf[20..28].try_into().unwrap()),
+            version: u32::from_be_bytes(buf[28..32].try_into().unwrap()),
+        });
+    }
+}
+
+// -- 被释放的page: [0~3]: 校验和, [4~7]: magic, [24~31]: 下一个空闲page, 0为结尾
+//      其余为0, 没有slot也没有page LSN, 被fetch时当作空page
+struct QcFreePage;
+
+impl QcFreePage {
+    const MAGIC: &'static [u8; 4] = b"QCFR";
+
+    fn encode(next: u64) -> [u8; PAGE_SIZE] {
+        let mut buf = [0_u8; PAGE_SIZE];
+        buf[4..8].copy_from_slice(Self::MAGIC);
+        buf[24..32].copy_from_slice(&next.to_be_bytes());
+        checksum::stamp_page(&mut buf);
+
+        return buf;
+    }
+
+    fn decode(buf: &[u8]) -> Option<u64> {
+        if &buf[4..8] != Self::MAGIC || !checksum::verify_page(buf) {
+            return None;
+        }
+
+        return Some(u64::from_be_bytes(buf[24..32].try_into().unwrap()));
+    }
+}
+
+// -- free list 的内存副本, 打开时从header遍历得到
+//      chain 为链表顺序, 末尾为链头
+#[derive(Debug, Default)]
+struct QcFreeList {
+    chain: Vec<PageId>,
+    set: HashSet<PageId>,
+}
+
+impl QcFreeList {
+    fn push(&mut self, page_id: PageId) {
+        self.chain.push(page_id);
+        self.set.insert(page_id);
+    }
+
+    // -- 从链头开始第一个满足 page_id % num == rem 的位置
+    fn find(&self, num: u64, rem: u64) -> Option<usize> {
+        self.chain.iter().rposition(|pid| pid.0 % num == rem)
+    }
+
+    fn remove(&mut self, idx: usize) -> PageId {
+        let pid = self.chain.remove(idx);
+        self.set.remove(&pid);
+        return pid;
+    }
+}
+
+#[derive(Debug)]
+pub struct QcFileDisk {
+    fd: File,
+    header: Mutex<QcDiskHeader>,
+    // -- 在header锁之后加锁
+    free: Mutex<QcFreeList>,
+}
+
+impl QcFileDisk {
+    // -- init_pages: 文件的初始大小, 已有文件只扩展不截断
+    pub fn open<T: AsRef<Path>>(path: T, init_pages: u64) -> io::Result<Self> {
+        use std::fs::OpenOptions;
+        let fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref())?;
+
+        let flen = fd.metadata()?.len();
+        let mut disk = QcFileDisk {
+            fd,
+            header: Mutex::new(QcDiskHeader {
+                next_page: 1,
+                free_head: 0,
+                checkpoint_lsn: INVALID_LSN,
+                version: FORMAT_VERSION,
+            }),
+            free: Mutex::new(QcFreeList::default()),
+        };
+
+        if flen == 0 {
+            // -- 新文件先写header再扩展, 不会留下没有header的非空文件
+            let hd = *disk.header.get_mut().unwrap();
+            disk.write_header(hd)?;
+        } else {
+            let mut buf = [0_u8; PAGE_SIZE];
+            disk.read_page(HEADER_PAGE_ID, &mut buf)?;
+
+            // -- 没有header的文件不是本库写的, 不能覆盖page 0上已有的数据
+            let Some(hd) = QcDiskHeader::decode(&buf) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "data file has no header page"));
+            };
+            // -- 旧格式的page没有校验和, 布局也不同, 不能当作损坏的page读
+            if hd.version != FORMAT_VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "data file format version {} is not supported, expected {FORMAT_VERSION}", hd.version)));
+            }
+            let free = disk.load_free_list(hd)?;
+            *disk.header.get_mut().unwrap() = hd;
+            *disk.free.get_mut().unwrap() = free;
+        }
+
+        let init_len = (PAGE_SIZE as u64) * init_pages;
+        if disk.fd.metadata()?.len() < init_len {
+            disk.fd.set_len(init_len)?;
+        }
+
+        return Ok(disk);
+    }
+
+    // -- 沿free list收集空闲page, 越界或成环说明文件已损坏
+    fn load_free_list(&self, hd: QcDiskHeader) -> io::Result<QcFreeList> {
+        let mut free = QcFreeList::default();
+        let mut buf = [0_u8; PAGE_SIZE];
+        let mut cur = hd.free_head;
+        while cur != 0 {
+            let pid = PageId(cur);
+            if cur >= hd.next_page || !free.set.insert(pid) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "free list is corrupted"));
+            }
+            free.chain.push(pid);
+
+            self.read_page(pid, &mut buf)?;
+            let Some(next) = QcFreePage::decode(&buf) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("free page {pid} is corrupted")));
+            };
+            cur = next;
+        }
+        free.chain.reverse();
+
+        return Ok(free);
+    }
+
+    fn write_free_page(&self, page_id: PageId, next: u64) -> io::Result<()> {
+        return self.write_page(page_id, &QcFreePage::encode(next));
+    }
+
+    pub(crate) fn offset(page_id: PageId) -> io::Result<u64> {
+        let Some(off) = page_id.0.checked_mul(PAGE_SIZE as u64) else {
+            return Err(io::Error::other("page id out of file range"));
+        };
+
+        return Ok(off);
+    }
+
+    pub(crate) fn raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    fn write_header(&self, hd: QcDiskHeader) -> io::Result<()> {
+        return self.write_page(HEADER_PAGE_ID, &hd.encode());
+    }
+}
+
+impl DiskManager for QcFileDisk {
+    fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> io::Result<()> {
+        let base = Self::offset(page_id)?;
+        let mut done = 0;
+        while done < buf.len() {
+            let n = self.fd.read_at(&mut buf[done..], base + done as u64)?;
+            if n == 0 {
+                break;
+            }
+            done += n;
+        }
+
+        buf[done..].fill(0);
+        return Ok(());
+    }
+
+    fn write_page(&self, page_id: PageId, buf: &[u8]) -> io::Result<()> {
+        return self.fd.write_all_at(buf, Self::offset(page_id)?);
+    }
+
+    fn allocate_page(&self) -> io::Result<PageId> {
+        return self.allocate_page_in(1, 0);
+    }
+
+    fn allocate_page_in(&self, num: u64, rem: u64) -> io::Result<PageId> {
+        assert!(rem < num, "page id remainder out of range");
+
+        let mut hd = self.header.lock().unwrap();
+        let mut free = self.free.lock().unwrap();
+        let mut nhd = *hd;
+
+        // -- 从free list中摘下, 前一个空闲page改为指向后一个
+        if let Some(idx) = free.find(num, rem) {
+            let next = if idx > 0 { free.chain[idx - 1].0 } else { 0 };
+            if idx + 1 == free.chain.len() {
+                nhd.free_head = next;
+                self.write_header(nhd)?;
+                *hd = nhd;
+            } else {
+                self.write_free_page(free.chain[idx + 1], next)?;
+            }
+
+            return Ok(free.remove(idx));
+        }
+
+        // -- 从文件末尾分配, 跳过的id放进free list留给其它分组
+        let mut skipped = Vec::new();
+        let pid = loop {
+            let pid = to_page_id(nhd.next_page)?;
+            nhd.next_page += 1;
+            if pid.0 % num == rem {
+                break pid;
+            }
+            skipped.push(pid);
+        };
+
+        let need_len = Self::offset(PageId(nhd.next_page))?;
+        if self.fd.metadata()?.len() < need_len {
+            self.fd.set_len(need_len)?;
+        }
+        for &sp in skipped.iter() {
+            self.write_free_page(sp, nhd.free_head)?;
+            nhd.free_head = sp.0;
+        }
+
+        self.write_header(nhd)?;
+        *hd = nhd;
+        for sp in skipped {
+            free.push(sp);
+        }
+
+        return Ok(pid);
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> io::Result<()> {
+        if page_id == HEADER_PAGE_ID {
+            return Err(io::Error::other("header page can not be deallocated"));
+        }
+
+        let mut hd = self.header.lock().unwrap();
+        let mut free = self.free.lock().unwrap();
+        if page_id.0 >= hd.next_page {
+            return Err(io::Error::other("page is not allocated"));
+        }
+        if free.set.contains(&page_id) {
+            return Err(io::Error::other("page is already deallocated"));
+        }
+        let mut nhd = *hd;
+
+        self.write_free_page(page_id, nhd.free_head)?;
+
+        nhd.free_head = page_id.0;
+        self.write_header(nhd)?;
+        *hd = nhd;
+        free.push(page_id);
+
+        return Ok(());
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        return self.fd.sync_all();
+    }
+
+    fn is_allocated(&self, page_id: PageId) -> bool {
+        let hd = self.header.lock().unwrap();
+        return page_id != HEADER_PAGE_ID
+            && page_id.0 < hd.next_page
+            && !self.free.lock().unwrap().set.contains(&page_id);
+    }
+
+    fn checkpoint_lsn(&self) -> io::Result<Lsn> {
+        return Ok(self.header.lock().unwrap().checkpoint_lsn);
+    }
+
+    fn set_checkpoint_lsn(&self, lsn: Lsn) -> io::Result<()> {
+        let mut hd = self.header.lock().unwrap();
+        let mut nhd = *hd;
+
+        nhd.checkpoint_lsn = lsn;
+        self.write_header(nhd)?;
+        self.fd.sync_data()?;
+        *hd = nhd;
+
+        return Ok(());
+    }
+
+    fn advise(&self, first: PageId, count: usize) {
+        let (Ok(off), Ok(len)) = (Self::offset(first), Self::offset(PageId(count as u64))) else {
+            return;
+        };
+        let (Ok(off), Ok(len)) = (libc::off_t::try_from(off), libc::off_t::try_from(len)) else {
+            return;
+        };
+
+        // -- 只是提示, 失败不影响读
+        unsafe {
+            libc::posix_fadvise(self.raw_fd(), off, len, libc::POSIX_FADV_WILLNEED);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QcMemoryInner {
+    pages: HashMap<PageId, Box<[u8]>>,
+    next_page: u64,
+    free: Vec<PageId>,
+}
+
+// -- 内存存储, 用于测试
+#[derive(Debug)]
+pub struct QcMemoryDisk {
+    inner: Mutex<QcMemoryInner>,
+}
+
+impl QcMemoryDisk {
+    pub fn new() -> Self {
+        QcMemoryDisk {
+            inner: Mutex::new(QcMemoryInner {
+                pages: HashMap::new(),
+                next_page: 1,
+                free: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl Default for QcMemoryDisk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiskManager for QcMemoryDisk {
+    fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> io::Result<()> {
+        let inner = self.inner.lock().unwrap();
+        match inner.pages.get(&page_id) {
+            Some(pg) => buf.copy_from_slice(pg),
+            None => buf.fill(0),
+        }
+
+        return Ok(());
+    }
+
+    fn write_page(&self, page_id: PageId, buf: &[u8]) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.pages.insert(page_id, buf.into());
+
+        return Ok(());
+    }
+
+    fn allocate_page(&self) -> io::Result<PageId> {
+        return self.allocate_page_in(1, 0);
+    }
+
+    fn allocate_page_in(&self, num: u64, rem: u64) -> io::Result<PageId> {
+        assert!(rem < num, "page id remainder out of range");
+
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(idx) = inner.free.iter().rposition(|pid| pid.0 % num == rem) {
+            return Ok(inner.free.remove(idx));
+        }
+
+        loop {
+            let pid = to_page_id(inner.next_page)?;
+            inner.next_page += 1;
+            if pid.0 % num == rem {
+                return Ok(pid);
+            }
+            inner.free.push(pid);
+        }
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> io::Result<()> {
+        if page_id == HEADER_PAGE_ID {
+            return Err(io::Error::other("header page can not be deallocated"));
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        if page_id.0 >= inner.next_page {
+            return Err(io::Error::other("page is not allocated"));
+        }
+        if inner.free.contains(&page_id) {
+            return Err(io::Error::other("page is already deallocated"));
+        }
+        inner.pages.remove(&page_id);
+        inner.free.push(page_id);
+
+        return Ok(());
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        return Ok(());
+    }
+
+    fn is_allocated(&self, page_id: PageId) -> bool {
+        let inner = self.inner.lock().unwrap();
+        return page_id != HEADER_PAGE_ID
+            && page_id.0 < inner.next_page
+            && !inner.free.contains(&page_id);
+    }
+}
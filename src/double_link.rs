@@ -1,31 +1,22 @@
-use std::ptr::NonNull;
+use std::{fmt::Display, ptr::NonNull};
 
-pub type QcTd = Option<NonNull<QcDLnode>>;
-
-pub fn parse_qctd(qc: QcTd) -> Option<i32> {
-    unsafe {
-       match qc {
-           Some(sp) => Some((*sp.as_ptr()).val),
-           None => None,
-       }
-    }
-}
+pub type QcTd<T> = Option<NonNull<QcDLnode<T>>>;
 
 #[derive(Debug)]
-pub struct QcDLnode {
-    prev: QcTd,
-    next: QcTd,
-    val: i32,
+pub struct QcDLnode<T> {
+    prev: QcTd<T>,
+    next: QcTd<T>,
+    val: T,
 }
 
 #[derive(Debug)]
-pub struct QcDoubleLink {
+pub struct QcDoubleLink<T> {
     size: usize,
-    head: QcTd,
-    tail: QcTd,
+    head: QcTd<T>,
+    tail: QcTd<T>,
 }
 
-impl QcDoubleLink {
+impl<T> QcDoubleLink<T> {
     pub fn new() -> Self {
         return QcDoubleLink {
             head: None,
@@ -34,7 +25,7 @@ impl QcDoubleLink {
         };
     }
 
-    pub fn push_back(&mut self, val: i32) -> QcTd {
+    pub fn push_back(&mut self, val: T) -> QcTd<T> {
         unsafe {
             let node = Box::new(QcDLnode::new(val, None, None));
             let pnode = Box::into_raw(node);
@@ -55,15 +46,13 @@ impl QcDoubleLink {
         }
     }
 
-    pub fn pop_front(&mut self) -> QcTd {
+    pub fn pop_front(&mut self) -> Option<T> {
+        let Some(phead) = self.head else {
+            return None;
+        };
+
         unsafe {
-            let out: QcTd;
-            if let Some(phead) = self.head {
-                out = Some(phead);
-                self.head = (*phead.as_ptr()).next.take();
-            } else {
-                out = None;
-            }
+            self.head = (*phead.as_ptr()).next.take();
 
             if let Some(nh) = self.head {
                 (*nh.as_ptr()).prev = None;
@@ -71,27 +60,57 @@ impl QcDoubleLink {
                 self.tail = None;
             }
 
-            return out;
+            self.size -= 1;
+
+            let node = Box::from_raw(phead.as_ptr());
+            return Some(node.val);
         }
     }
 
-    pub fn remove_item(qc: QcTd) {
+    // -- qc must be a node of this link, it is freed after removed
+    pub fn remove_item(&mut self, qc: QcTd<T>) -> Option<T> {
+        let Some(q) = qc else {
+            return None;
+        };
+
         unsafe {
-            if let Some(q) = qc {
-                let prev_node = (*q.as_ptr()).prev;
-                let next_node = (*q.as_ptr()).next;
+            let prev_node = (*q.as_ptr()).prev.take();
+            let next_node = (*q.as_ptr()).next.take();
 
-                if let Some(pn) = prev_node {
-                    (*pn.as_ptr()).next = (*q.as_ptr()).next.take();
-                }
-                if let Some(nn) = next_node {
-                    (*nn.as_ptr()).prev = (*q.as_ptr()).prev.take();
+            match prev_node {
+                Some(pn) => (*pn.as_ptr()).next = next_node,
+                None => self.head = next_node,
+            }
+            match next_node {
+                Some(nn) => (*nn.as_ptr()).prev = prev_node,
+                None => self.tail = prev_node,
+            }
+
+            self.size -= 1;
+
+            let node = Box::from_raw(q.as_ptr());
+            return Some(node.val);
+        }
+    }
+
+    // -- 从头开始找第一个满足条件的节点
+    pub fn find<F: Fn(&T) -> bool>(&self, pred: F) -> QcTd<T> {
+        unsafe {
+            let mut th = self.head;
+            while let Some(p) = th {
+                let tp = &*p.as_ptr();
+                if pred(&tp.val) {
+                    return Some(p);
                 }
+                th = tp.next;
             }
+
+            return None;
         }
     }
 
-    pub fn reset_item(qc: QcTd, val: i32) {
+    #[allow(dead_code)]
+    pub fn reset_item(qc: QcTd<T>, val: T) {
         unsafe {
             if let Some(q) = qc {
                 (*q.as_ptr()).val = val;
@@ -99,6 +118,12 @@ impl QcDoubleLink {
         }
     }
 
+    pub fn len(&self) -> usize {
+        return self.size;
+    }
+}
+
+impl<T: Display> QcDoubleLink<T> {
     pub fn report(&self) {
         unsafe {
             if self.size == 0 {
@@ -124,14 +149,16 @@ impl QcDoubleLink {
             println!();
         }
     }
+}
 
-    pub fn len(&self) -> usize {
-        return self.size;
+impl<T> Drop for QcDoubleLink<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
     }
 }
 
-impl QcDLnode {
-    pub fn new(val: i32, prev: QcTd, next: QcTd) -> Self {
+impl<T> QcDLnode<T> {
+    pub fn new(val: T, prev: QcTd<T>, next: QcTd<T>) -> Self {
         return QcDLnode {
             prev,
             next,
@@ -0,0 +1,187 @@
+use std::{ffi::OsString, fs::{File, OpenOptions}, io, os::unix::fs::FileExt, path::{Path, PathBuf}, sync::Mutex};
+
+use crate::{checksum, disk::DiskManager, page::PAGE_SIZE, trace::PageId, wal::Lsn};
+
+// -- double-write文件布局:
+//      [0~7]: 本批page数, 之后每项 [0~7]: page id, [8~]: page内容
+const ENTRY_SIZE: usize = 8 + PAGE_SIZE;
+
+struct QcDwbState {
+    fd: File,
+    // -- 上一批原地写入还没sync
+    pending: bool,
+}
+
+// -- 写page前先把整批写入double-write文件并sync, 再原地写入
+//      原地写到一半崩溃时, 打开时用double-write中的副本恢复校验失败的page
+pub struct QcDoubleWriteDisk {
+    disk: Box<dyn DiskManager>,
+    dwb: Mutex<QcDwbState>,
+    restored: usize,
+}
+
+impl QcDoubleWriteDisk {
+    // -- 数据文件对应的double-write文件: <path>.dwb
+    pub fn dwb_path<T: AsRef<Path>>(path: T) -> PathBuf {
+        let mut os = OsString::from(path.as_ref().as_os_str());
+        os.push(".dwb");
+        return PathBuf::from(os);
+    }
+
+    pub fn open<T: AsRef<Path>>(disk: Box<dyn DiskManager>, dwb_path: T) -> io::Result<Self> {
+        let fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dwb_path.as_ref())?;
+
+        let mut dw = QcDoubleWriteDisk {
+            disk,
+            dwb: Mutex::new(QcDwbState {
+                fd,
+                pending: false,
+            }),
+            restored: 0,
+        };
+        dw.restored = dw.restore()?;
+
+        return Ok(dw);
+    }
+
+    // -- 打开时恢复的page数
+    pub fn restored(&self) -> usize {
+        self.restored
+    }
+
+    fn restore(&self) -> io::Result<usize> {
+        let st = self.dwb.lock().unwrap();
+        let flen = st.fd.metadata()?.len();
+
+        let mut restored = 0;
+        if flen >= 8 {
+            let mut head = [0_u8; 8];
+            st.fd.read_exact_at(&mut head, 0)?;
+            let count = u64::from_be_bytes(head);
+
+            let mut entry = vec![0_u8; ENTRY_SIZE];
+            let mut cur = vec![0_u8; PAGE_SIZE];
+            for i in 0..count {
+                let off = 8 + i * ENTRY_SIZE as u64;
+                if off + ENTRY_SIZE as u64 > flen {
+                    break;
+                }
+                st.fd.read_exact_at(&mut entry, off)?;
+
+                // -- 副本本身不完整则跳过: 那时原地写入还没开始
+                let pid = PageId(u64::from_be_bytes(entry[0..8].try_into().unwrap()));
+                let copy = &entry[8..];
+                if !checksum::verify_page(copy) {
+                    continue;
+                }
+
+                // -- 之后被释放的page已串进free list, 不能用旧副本覆盖
+                if !self.disk.is_allocated(pid) {
+                    continue;
+                }
+
+                self.disk.read_page(pid, &mut cur)?;
+                if checksum::verify_page(&cur) {
+                    continue;
+                }
+
+                self.disk.write_page(pid, copy)?;
+                restored += 1;
+            }
+        }
+
+        if restored > 0 {
+            self.disk.sync()?;
+        }
+        st.fd.set_len(0)?;
+        st.fd.sync_all()?;
+
+        return Ok(restored);
+    }
+}
+
+impl DiskManager for QcDoubleWriteDisk {
+    fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> io::Result<()> {
+        return self.disk.read_page(page_id, buf);
+    }
+
+    fn write_page(&self, page_id: PageId, buf: &[u8]) -> io::Result<()> {
+        return self.write_pages(&[(page_id, buf)]);
+    }
+
+    fn allocate_page(&self) -> io::Result<PageId> {
+        return self.disk.allocate_page();
+    }
+
+    fn allocate_page_in(&self, num: u64, rem: u64) -> io::Result<PageId> {
+        return self.disk.allocate_page_in(num, rem);
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> io::Result<()> {
+        return self.disk.deallocate_page(page_id);
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        let mut st = self.dwb.lock().unwrap();
+        self.disk.sync()?;
+
+        // -- 原地写入已落盘, 作废这一批副本
+        if st.pending {
+            st.fd.write_all_at(&0_u64.to_be_bytes(), 0)?;
+        }
+        st.pending = false;
+
+        return Ok(());
+    }
+
+    fn is_allocated(&self, page_id: PageId) -> bool {
+        return self.disk.is_allocated(page_id);
+    }
+
+    fn advise(&self, first: PageId, count: usize) {
+        self.disk.advise(first, count);
+    }
+
+    fn checkpoint_lsn(&self) -> io::Result<Lsn> {
+        return self.disk.checkpoint_lsn();
+    }
+
+    fn set_checkpoint_lsn(&self, lsn: Lsn) -> io::Result<()> {
+        return self.disk.set_checkpoint_lsn(lsn);
+    }
+
+    fn read_pages(&self, reqs: &mut [(PageId, &mut [u8])]) -> io::Result<()> {
+        return self.disk.read_pages(reqs);
+    }
+
+    fn write_pages(&self, reqs: &[(PageId, &[u8])]) -> io::Result<()> {
+        if reqs.is_empty() {
+            return Ok(());
+        }
+
+        let mut st = self.dwb.lock().unwrap();
+
+        // -- 上一批原地写入落盘后才能覆盖double-write文件
+        if st.pending {
+            self.disk.sync()?;
+            st.pending = false;
+        }
+
+        let mut buf = Vec::with_capacity(8 + reqs.len() * ENTRY_SIZE);
+        buf.extend_from_slice(&(reqs.len() as u64).to_be_bytes());
+        for (pid, page) in reqs.iter() {
+            buf.extend_from_slice(&pid.0.to_be_bytes());
+            buf.extend_from_slice(page);
+        }
+        st.fd.write_all_at(&buf, 0)?;
+        st.fd.sync_data()?;
+
+        st.pending = true;
+        return self.disk.write_pages(reqs);
+    }
+}
@@ -1,10 +1,43 @@
+use crate::trace::PageId;
+
 #[derive(Debug)]
-pub struct QcBupoError;
+pub enum QcBupoError {
+    // -- page is still pinned by someone
+    PagePinned,
+    // -- every frame is pinned, no victim found
+    NoFreeFrame,
+    // -- page id can not be fetched or deleted (e.g. the header page, a page not allocated)
+    InvalidPage,
+    // -- try-fetch: frame latch is held by someone else
+    LatchBusy,
+    // -- page read from disk failed its checksum
+    Corruption(PageId),
+    // -- page is locked by another active transaction
+    TxnConflict(PageId),
+    // -- builder options conflict or are out of range
+    InvalidConfig(&'static str),
+    Io(std::io::Error),
+}
 
 impl std::fmt::Display for QcBupoError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "QcBupoError")
+        match self {
+            QcBupoError::PagePinned => write!(f, "QcBupoError: page is pinned"),
+            QcBupoError::NoFreeFrame => write!(f, "QcBupoError: all frames are pinned"),
+            QcBupoError::InvalidPage => write!(f, "QcBupoError: invalid page id"),
+            QcBupoError::LatchBusy => write!(f, "QcBupoError: page latch is busy"),
+            QcBupoError::Corruption(pid) => write!(f, "QcBupoError: page {pid} checksum mismatch"),
+            QcBupoError::TxnConflict(pid) => write!(f, "QcBupoError: page {pid} is locked by another transaction"),
+            QcBupoError::InvalidConfig(msg) => write!(f, "QcBupoError: invalid config: {msg}"),
+            QcBupoError::Io(e) => write!(f, "QcBupoError: io: {e}"),
+        }
     }
 }
 
 impl std::error::Error for QcBupoError {}
+
+impl From<std::io::Error> for QcBupoError {
+    fn from(e: std::io::Error) -> Self {
+        QcBupoError::Io(e)
+    }
+}
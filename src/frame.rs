@@ -1,7 +1,7 @@
 use std::{cell::RefCell, collections::{HashMap, VecDeque}, error::Error, fs::File, io, os::unix::fs::{FileExt, OpenOptionsExt}, path::Path, rc::Rc};
 
 use crate::{error::QcBupoError, page::QcPager};
-use crate::trace::QcTracer;
+use crate::trace::{PageId, QcTracer};
 
 const PG_NUM: u8 = 16;
 const FM_NUM: u8 = 4;
@@ -9,8 +9,8 @@ const FM_NUM: u8 = 4;
 pub struct QcBupo {
     tracer: Box<QcTracer>,              // extern replace tracer
     frame: Vec<Rc<RefCell<QcPager>>>,     // frame list
-    pchain: VecDeque<u8>,               // waiting fan-out
-    pg_tbl: HashMap<u8, (usize, u32)>,  // <page_id> => (<frame_id>, <ref_count>)
+    pchain: VecDeque<PageId>,           // waiting fan-out
+    pg_tbl: HashMap<PageId, (usize, u32)>,  // <page_id> => (<frame_id>, <ref_count>)
     fd: Box<File>,
 }
 
@@ -43,7 +43,7 @@ impl QcBupo {
     }
 
     // -- 获取page
-    pub fn fetch_page(&mut self, page_id: u8) -> Result<Rc<RefCell<QcPager>>, io::Error> {
+    pub fn fetch_page(&mut self, page_id: PageId) -> Result<Rc<RefCell<QcPager>>, io::Error> {
         if let Some(pinfo) = self.pg_tbl.get_mut(&page_id) {
             // 存在
             pinfo.1 += 1;
@@ -56,7 +56,7 @@ impl QcBupo {
 
             let result = self.fd.read_at(
                 &mut self.frame[fid].borrow_mut().mut_buffer(),
-                (page_id as u64 - 1) * 4096,
+                (page_id.0 - 1) * 4096,
             );
 
             return match result {
@@ -68,7 +68,7 @@ impl QcBupo {
     }
 
     // -- unpin
-    pub fn unpin_page(&mut self, page_id: u8) -> Option<()> {
+    pub fn unpin_page(&mut self, page_id: PageId) -> Option<()> {
         let Some(pinfo) = self.pg_tbl.get_mut(&page_id) else {
             return None;
         };
@@ -83,7 +83,7 @@ impl QcBupo {
     }
 
     // -- 刷盘
-    pub fn flush_page(&mut self, page_id: u8) -> Result<(), Box<dyn Error>> {
+    pub fn flush_page(&mut self, page_id: PageId) -> Result<(), Box<dyn Error>> {
         let Some(&pinfo) = self.pg_tbl.get(&page_id) else {
             return Err(Box::new(QcBupoError));
         };
@@ -91,7 +91,7 @@ impl QcBupo {
         let frame_id = pinfo.0;
         let result = self.fd.write_at(
             &self.frame[frame_id].borrow_mut().buffer(),
-            (page_id as u64 - 1) * 4096,
+            (page_id.0 - 1) * 4096,
         );
 
         return match result {
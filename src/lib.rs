@@ -1,11 +1,34 @@
+#![allow(clippy::needless_return, clippy::question_mark)]
+
 mod double_link;
 
 pub mod error;
 pub mod page;
+pub mod checksum;
 pub mod trace;
+pub mod replacer;
+pub mod lru_k;
+pub mod clock;
+pub mod arc;
+pub mod two_queue;
 
 pub mod buffpool;
+pub mod config;
+pub mod cleaner;
+mod read_ahead;
+pub mod page_guard;
+pub mod strategy;
+pub mod parallel;
 pub mod bitmap;
+mod page_table;
+pub mod disk;
+pub mod double_write;
+pub mod wal;
+pub mod recovery;
+pub mod checkpoint;
+pub mod txn;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
 
 
 // --- XXX: Unused history code ---
@@ -14,14 +37,62 @@ pub mod bitmap;
 
 #[cfg(test)]
 mod tests {
+    use arc::QcArcReplacer;
     use bitmap::Qcbitmap;
+    use cleaner::QcCleanerConfig;
+    use clock::QcClockReplacer;
+    use config::{QcBuffpoolConfig, QcReplacerKind, QcSyncPolicy};
+    use disk::{DiskManager, QcFileDisk, QcMemoryDisk};
     use buffpool::QcBuffpool;
+    use checkpoint::QcCheckpointConfig;
     use double_link::QcDoubleLink;
+    use double_write::QcDoubleWriteDisk;
+    use error::QcBupoError;
+    use lru_k::QcLruKReplacer;
     use page::QcPager;
-    use trace::QcTracer;
+    use replacer::Replacer;
+    use strategy::QcAccessStrategy;
+    use trace::{PageId, QcTracer};
+    use two_queue::QcTwoQueueReplacer;
+    use txn::QcTransactionManager;
+    use wal::{QcLogBody, QcLogManager, QcLogRecord, INVALID_LSN};
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
 
     use super::*;
 
+    // -- 测试用的数据文件放在系统临时目录, 按进程区分; 离开作用域时连同.wal/.dwb一起删除
+    struct TmpDb(std::path::PathBuf);
+
+    impl TmpDb {
+        fn new(name: &str) -> Self {
+            let tmp = TmpDb(std::env::temp_dir().join(format!("qc_bufpo_{}_{name}.db", std::process::id())));
+            tmp.clean();
+            return tmp;
+        }
+
+        fn path(&self) -> &std::path::Path {
+            return &self.0;
+        }
+
+        fn wal(&self) -> std::path::PathBuf {
+            let mut os = self.0.clone().into_os_string();
+            os.push(".wal");
+            return os.into();
+        }
+
+        fn clean(&self) {
+            let _ = std::fs::remove_file(&self.0);
+            let _ = std::fs::remove_file(self.wal());
+            let _ = std::fs::remove_file(QcDoubleWriteDisk::dwb_path(&self.0));
+        }
+    }
+
+    impl Drop for TmpDb {
+        fn drop(&mut self) {
+            self.clean();
+        }
+    }
+
     #[test]
     fn test_bitmap() {
         let mut bitm = Qcbitmap::new(10);
@@ -79,51 +150,1342 @@ mod tests {
         dpk.report();
 
         // dpk.pop_front();
-        QcDoubleLink::remove_item(ko);
+        dpk.remove_item(ko);
         dpk.report();
     }
 
     #[test]
     fn test_tracer() {
-        let mut tracer = QcTracer::new();
+        let mut tracer = QcTracer::new(4);
 
-        tracer.insert(12);
+        for fid in [2, 0, 1] {
+            tracer.record_access(fid, PageId(fid as u64));
+            tracer.set_evictable(fid, true);
+        }
         tracer.report();
 
-        tracer.insert(10);
-        tracer.report();
-        tracer.insert(5);
-        tracer.report();
-        tracer.insert(10);
-        tracer.report();
-        tracer.insert(11);
+        // -- frame 2 被访问后移到队尾
+        tracer.record_access(2, PageId(2));
+        tracer.set_evictable(3, true);
+        tracer.set_evictable(0, false);
         tracer.report();
+        assert_eq!(tracer.size(), 3);
+
+        assert_eq!(tracer.evict(), Some(1));
+        assert_eq!(tracer.evict(), Some(2));
+        assert_eq!(tracer.evict(), Some(3));
+        assert_eq!(tracer.evict(), None);
     }
 
 
+    #[test]
+    fn test_lru_k() {
+        let mut lruk = QcLruKReplacer::new(8, 2);
+
+        // -- frame 0 被扫描访问一次, 1/2 各访问两次
+        for fid in [1, 2, 0, 1, 2] {
+            lruk.record_access(fid, PageId(fid as u64));
+        }
+        for fid in 0..3 {
+            lruk.set_evictable(fid, true);
+        }
+        lruk.report();
+        assert_eq!(lruk.size(), 3);
+
+        // -- 不足k次的最先淘汰
+        assert_eq!(lruk.evict(), Some(0));
+
+        // -- 1 的第2近访问更早
+        assert_eq!(lruk.evict(), Some(1));
+
+        lruk.record_access(3, PageId(3));
+        lruk.set_evictable(2, false);
+        lruk.set_evictable(3, true);
+        assert_eq!(lruk.evict(), Some(3));
+        assert_eq!(lruk.evict(), None);
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut clock = QcClockReplacer::new(4);
+
+        for fid in 0..4 {
+            clock.record_access(fid, PageId(fid as u64));
+            clock.set_evictable(fid, true);
+        }
+        clock.set_evictable(2, false);
+        clock.report();
+
+        // -- 第一圈全部清零, 第二圈从0开始淘汰
+        assert_eq!(clock.evict(), Some(0));
+
+        // -- 1 再次被访问, 获得second chance
+        clock.record_access(1, PageId(1));
+        assert_eq!(clock.evict(), Some(3));
+        assert_eq!(clock.evict(), Some(1));
+        assert_eq!(clock.evict(), None);
+        assert_eq!(clock.size(), 0);
+    }
+
+    #[test]
+    fn test_buffpool_clock() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let disk = SlowDisk {
+            disk: QcMemoryDisk::new(),
+            delay: std::time::Duration::ZERO,
+            reads: Arc::clone(&reads),
+            batches: Arc::new(AtomicUsize::new(0)),
+            advised: Arc::new(AtomicUsize::new(0)),
+        };
+        for _ in 0..5 {
+            disk.allocate_page().unwrap();
+        }
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(3)
+            .replacer(QcReplacerKind::Clock)
+            .build_with_disk(Box::new(disk))
+            .unwrap();
+
+        let touch = |pid: u64| {
+            bufpool.fetch_page(PageId(pid)).unwrap();
+            bufpool.unpin_page(PageId(pid)).unwrap();
+        };
+
+        for pid in [1, 2, 3] {
+            touch(pid);
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+
+        // -- 引用位全为1, 清一圈后淘汰frame 0中的page 1
+        touch(4);
+        touch(2);
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+
+        // -- 指针停在frame 1, page 2 有second chance, 淘汰page 3
+        touch(5);
+        bufpool.report();
+        assert_eq!(reads.load(Ordering::SeqCst), 5);
+
+        touch(2);
+        touch(4);
+        assert_eq!(reads.load(Ordering::SeqCst), 5);
+        touch(3);
+        assert_eq!(reads.load(Ordering::SeqCst), 6);
+        touch(1);
+        assert_eq!(reads.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn test_arc() {
+        let mut arc = QcArcReplacer::new(3);
+
+        for (fid, pid) in [(0, 10), (1, 11), (2, 12)] {
+            arc.record_access(fid, PageId(pid));
+            arc.set_evictable(fid, true);
+        }
+        // -- 10 访问两次进入T2
+        arc.record_access(0, PageId(10));
+        arc.report();
+
+        assert_eq!(arc.evict(), Some(1));
+
+        // -- 11 命中B1, p增大
+        arc.record_access(1, PageId(11));
+        arc.set_evictable(1, true);
+        assert_eq!(arc.target(), 1);
+        arc.report();
+
+        assert_eq!(arc.evict(), Some(2));
+        assert_eq!(arc.evict(), Some(0));
+
+        // -- 10 命中B2, p减小
+        arc.record_access(0, PageId(10));
+        assert_eq!(arc.target(), 0);
+        arc.report();
+
+        assert_eq!(arc.size(), 1);
+        assert_eq!(arc.evict(), Some(1));
+        assert_eq!(arc.evict(), None);
+    }
+
+    #[test]
+    fn test_two_queue() {
+        let mut twoq = QcTwoQueueReplacer::new(4, 0.25, 0.5);
+
+        for (fid, pid) in [(0, 10), (1, 11), (2, 12), (3, 13)] {
+            twoq.record_access(fid, PageId(pid));
+            twoq.set_evictable(fid, true);
+        }
+        twoq.report();
+
+        // -- A1in 超过kin, 按FIFO淘汰, 10进入A1out
+        assert_eq!(twoq.evict(), Some(0));
+
+        // -- 10 命中A1out, 直接进入Am
+        twoq.record_access(0, PageId(10));
+        twoq.set_evictable(0, true);
+        twoq.report();
+
+        assert_eq!(twoq.evict(), Some(1));
+        assert_eq!(twoq.evict(), Some(2));
+        // -- A1in 不超过kin时优先淘汰Am
+        assert_eq!(twoq.evict(), Some(0));
+        assert_eq!(twoq.evict(), Some(3));
+        assert_eq!(twoq.evict(), None);
+    }
+
     #[test]
     fn test_buffpool() {
-        let mut bufpool = QcBuffpool::new(8);
-        bufpool.fetch_page(1);
-        let pg = bufpool.fetch_page(2).upgrade().unwrap();
-        pg.lock().unwrap().save(10, "hsdfp".to_string());
-        pg.lock().unwrap().save(5, "klusfq".to_string());
-        pg.lock().unwrap().report();
+        let tmp = TmpDb::new("buffpool");
+        let bufpool = QcBuffpoolConfig::new(tmp.path()).pool_size(8).build().unwrap();
+        for _ in 0..12 {
+            bufpool.new_page().unwrap();
+        }
+        bufpool.fetch_page(PageId(1)).unwrap();
+        let pg = bufpool.fetch_page(PageId(2)).unwrap().upgrade().unwrap();
+        pg.write().unwrap().save(10, "hsdfp".to_string());
+        pg.write().unwrap().save(5, "klusfq".to_string());
+        pg.read().unwrap().report();
         bufpool.report();
         drop(pg);
         bufpool.report();
-        bufpool.flush_page(2).unwrap();
-
-        bufpool.fetch_page(7);
-        bufpool.fetch_page(8);
-        bufpool.fetch_page(9);
-        bufpool.fetch_page(5);
-        bufpool.fetch_page(7);
-        bufpool.fetch_page(12);
-        let kg = bufpool.fetch_page(6).upgrade().unwrap();
-        kg.lock().unwrap().save(12, "this ok".to_string());
+        bufpool.flush_page(PageId(2)).unwrap();
+        bufpool.unpin_page(PageId(2)).unwrap();
+
+        bufpool.fetch_page(PageId(7)).unwrap();
+        bufpool.fetch_page(PageId(8)).unwrap();
+        bufpool.fetch_page(PageId(9)).unwrap();
+        bufpool.fetch_page(PageId(5)).unwrap();
+        bufpool.fetch_page(PageId(7)).unwrap();
+        bufpool.fetch_page(PageId(12)).unwrap();
+        let kg = bufpool.fetch_page(PageId(6)).unwrap().upgrade().unwrap();
+        kg.write().unwrap().save(12, "this ok".to_string());
         drop(kg);
         bufpool.report();
-        // bufpool.fetch_page(1);
+        // bufpool.fetch_page(PageId(1));
+    }
+
+    #[test]
+    fn test_buffpool_evict() {
+        let disk = QcMemoryDisk::new();
+        for _ in 0..5 {
+            disk.allocate_page().unwrap();
+        }
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(2)
+            .build_with_disk(Box::new(disk))
+            .unwrap();
+
+        let pg = bufpool.fetch_page(PageId(3)).unwrap().upgrade().unwrap();
+        pg.write().unwrap().save(1, "evict me".to_string());
+        drop(pg);
+        bufpool.fetch_page(PageId(4)).unwrap();
+
+        // -- all frames pinned
+        assert!(bufpool.fetch_page(PageId(5)).is_err());
+
+        // -- page 3 is dirty, written back when evicted
+        assert_eq!(bufpool.dirty_count(), 0);
+        bufpool.unpin_page(PageId(3)).unwrap();
+        assert_eq!(bufpool.dirty_count(), 1);
+        bufpool.fetch_page(PageId(5)).unwrap();
+        bufpool.report();
+
+        bufpool.unpin_page(PageId(4)).unwrap();
+        let pg = bufpool.fetch_page(PageId(3)).unwrap().upgrade().unwrap();
+        assert_eq!(pg.read().unwrap().obtain(1), Some("evict me".to_string()));
+    }
+
+    #[test]
+    fn test_page_guard() {
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(1)
+            .build()
+            .unwrap();
+
+        let pa = {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(7, "guarded".to_string());
+            assert!(wg.is_dirty());
+            wg.page_id()
+        };
+
+        // -- guard已unpin, 可以淘汰pa
+        {
+            let rg = bufpool.new_page().unwrap();
+            assert_ne!(rg.page_id(), pa);
+            assert!(bufpool.fetch_page(pa).is_err());
+        }
+
+        let rg = bufpool.fetch_page_read(pa).unwrap();
+        assert_eq!(rg.obtain(7), Some("guarded".to_string()));
+        assert!(!rg.is_dirty());
+        drop(rg);
+        bufpool.report();
+    }
+
+    #[test]
+    fn test_buffpool_config() {
+        let tmp = TmpDb::new("config");
+
+        let bufpool = QcBuffpoolConfig::new(tmp.path())
+            .file_pages(64)
+            .pool_size(4)
+            .replacer(QcReplacerKind::LruK(2))
+            .sync_policy(QcSyncPolicy::Always)
+            .build()
+            .unwrap();
+        assert_eq!(std::fs::metadata(tmp.path()).unwrap().len(), 4096 * 64);
+
+        let mut pids = Vec::new();
+        for k in 0..6 {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(k, format!("page {k}"));
+            pids.push(wg.page_id());
+        }
+        drop(bufpool);
+
+        // -- 重新打开, 不截断已有文件
+        let bufpool = QcBuffpoolConfig::new(tmp.path())
+            .file_pages(16)
+            .build()
+            .unwrap();
+        assert_eq!(std::fs::metadata(tmp.path()).unwrap().len(), 4096 * 64);
+
+        let rg = bufpool.fetch_page_read(pids[1]).unwrap();
+        assert_eq!(rg.obtain(1), Some("page 1".to_string()));
+        drop(rg);
+
+        // -- 冲突或越界的配置在build时报错
+        let bad = [
+            QcBuffpoolConfig::memory().pool_size(0).build().err(),
+            QcBuffpoolConfig::memory().checkpoint(QcCheckpointConfig::default()).build().err(),
+            QcBuffpoolConfig::new(tmp.path()).wal(tmp.wal()).checkpoint(QcCheckpointConfig { log_bytes: Some(0), ..Default::default() }).build().err(),
+            QcBuffpoolConfig::new(tmp.path()).wal(tmp.wal()).checkpoint(QcCheckpointConfig { interval: Some(std::time::Duration::ZERO), ..Default::default() }).build().err(),
+            QcBuffpoolConfig::memory().double_write(true).build().err(),
+            QcBuffpoolConfig::new(tmp.path()).double_write(true).build_with_disk(Box::new(QcMemoryDisk::new())).err(),
+            QcBuffpoolConfig::memory().build_parallel(0).err(),
+            QcBuffpoolConfig::memory().replacer(QcReplacerKind::LruK(0)).build().err(),
+            QcBuffpoolConfig::memory().replacer(QcReplacerKind::TwoQueue { kin_ratio: 1.5, kout_ratio: 0.5 }).build().err(),
+            QcBuffpoolConfig::memory().replacer(QcReplacerKind::TwoQueue { kin_ratio: 0.25, kout_ratio: -1.0 }).build().err(),
+            QcBuffpoolConfig::memory().cleaner(QcCleanerConfig { dirty_watermark: 0.0, ..Default::default() }).build().err(),
+            QcBuffpoolConfig::memory().cleaner(QcCleanerConfig { dirty_watermark: f64::NAN, ..Default::default() }).build().err(),
+            QcBuffpoolConfig::memory().cleaner(QcCleanerConfig { interval: std::time::Duration::ZERO, ..Default::default() }).build().err(),
+        ];
+        for err in bad {
+            println!("{err:?}");
+            assert!(matches!(err, Some(QcBupoError::InvalidConfig(_))));
+        }
+    }
+
+    #[test]
+    fn test_memory_disk() {
+        let disk = QcMemoryDisk::new();
+        let mut buf = [7_u8; page::PAGE_SIZE];
+
+        disk.read_page(PageId(3), &mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == 0));
+
+        assert_eq!(disk.allocate_page().unwrap(), PageId(1));
+        assert_eq!(disk.allocate_page().unwrap(), PageId(2));
+        assert_eq!(disk.allocate_page().unwrap(), PageId(3));
+        let mut pager = QcPager::new();
+        pager.save(1, "on disk".to_string());
+        pager.set_checksum();
+        disk.write_page(PageId(3), pager.buffer()).unwrap();
+        disk.deallocate_page(PageId(2)).unwrap();
+        assert!(disk.deallocate_page(PageId(2)).is_err());
+        assert!(disk.deallocate_page(PageId(4)).is_err());
+        assert!(!disk.is_allocated(PageId(4)));
+        assert_eq!(disk.allocate_page().unwrap(), PageId(2));
+
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(1)
+            .build_with_disk(Box::new(disk))
+            .unwrap();
+        assert_eq!(bufpool.fetch_page_read(PageId(3)).unwrap().obtain(1), Some("on disk".to_string()));
+
+        // -- 未分配的page id不能读入, 也不能预读
+        assert!(matches!(bufpool.fetch_page_read(PageId(1 << 40)).err(), Some(QcBupoError::InvalidPage)));
+        assert!(matches!(bufpool.prefetch(PageId(3)..PageId(5)).err(), Some(QcBupoError::InvalidPage)));
+
+        // -- 先fetch一个未分配的id, 之后new_page分到它时不能与page表冲突
+        let bufpool = QcBuffpoolConfig::memory().pool_size(1).build().unwrap();
+        assert!(matches!(bufpool.fetch_page(PageId(1)).err(), Some(QcBupoError::InvalidPage)));
+        assert_eq!(bufpool.new_page().unwrap().page_id(), PageId(1));
+        assert_eq!(bufpool.new_page().unwrap().page_id(), PageId(2));
+        bufpool.fetch_page_read(PageId(1)).unwrap();
+    }
+
+    #[test]
+    fn test_new_delete_page() {
+        let tmp = TmpDb::new("alloc");
+
+        let bufpool = QcBuffpoolConfig::new(tmp.path())
+            .pool_size(2)
+            .build()
+            .unwrap();
+
+        let mut pids = Vec::new();
+        for v in ["a", "b", "c"] {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, v.to_string());
+            pids.push(wg.page_id());
+        }
+        assert_eq!(pids, vec![PageId(1), PageId(2), PageId(3)]);
+        assert!(bufpool.fetch_page(disk::HEADER_PAGE_ID).is_err());
+        assert!(bufpool.fetch_page(PageId::INVALID).is_err());
+
+        {
+            let _rg = bufpool.fetch_page_read(PageId(2)).unwrap();
+            assert!(bufpool.delete_page(PageId(2)).is_err());
+        }
+        bufpool.delete_page(PageId(2)).unwrap();
+
+        // -- 重复删除与从未分配的page被拒绝
+        assert!(matches!(bufpool.delete_page(PageId(2)), Err(QcBupoError::InvalidPage)));
+        assert!(matches!(bufpool.delete_page(PageId(999)), Err(QcBupoError::InvalidPage)));
+
+        // -- 已释放的page不能再读入, 否则写回会覆盖free list的链接
+        assert!(matches!(bufpool.fetch_page_write(PageId(2)).err(), Some(QcBupoError::InvalidPage)));
+        drop(bufpool);
+
+        // -- 被释放的page也带校验和
+        let disk = QcFileDisk::open(tmp.path(), 0).unwrap();
+        let mut buf = vec![0_u8; page::PAGE_SIZE];
+        disk.read_page(PageId(2), &mut buf).unwrap();
+        assert!(checksum::verify_page(&buf));
+        assert!(buf.iter().any(|&b| b != 0));
+        drop(disk);
+
+        // -- free list 持久化在header page中
+        let bufpool = QcBuffpoolConfig::new(tmp.path())
+            .pool_size(2)
+            .build()
+            .unwrap();
+        assert_eq!(bufpool.new_page().unwrap().page_id(), PageId(2));
+        assert_eq!(bufpool.new_page().unwrap().page_id(), PageId(4));
+        assert_eq!(bufpool.fetch_page_read(PageId(1)).unwrap().obtain(1), Some("a".to_string()));
+
+        // -- 没有header的文件拒绝打开, page 0 不被覆盖
+        let raw = TmpDb::new("no_header");
+        std::fs::write(raw.path(), vec![7_u8; page::PAGE_SIZE * 2]).unwrap();
+        assert!(QcFileDisk::open(raw.path(), 0).is_err());
+        assert_eq!(std::fs::read(raw.path()).unwrap(), vec![7_u8; page::PAGE_SIZE * 2]);
+
+        // -- 按分组分配: 从free list中间摘下, 从末尾分配时跳过的id进入free list
+        let striped = TmpDb::new("alloc_in");
+        let disk = QcFileDisk::open(striped.path(), 0).unwrap();
+        for _ in 0..6 {
+            disk.allocate_page().unwrap();
+        }
+        for raw in [2, 3, 4] {
+            disk.deallocate_page(PageId(raw)).unwrap();
+        }
+        assert_eq!(disk.allocate_page_in(2, 1).unwrap(), PageId(3));
+        drop(disk);
+
+        let disk = QcFileDisk::open(striped.path(), 0).unwrap();
+        assert!(disk.is_allocated(PageId(3)));
+        assert!(!disk.is_allocated(PageId(2)) && !disk.is_allocated(PageId(4)));
+        assert_eq!(disk.allocate_page_in(4, 1).unwrap(), PageId(9));
+        let rest: Vec<PageId> = (0..5).map(|_| disk.allocate_page().unwrap()).collect();
+        assert_eq!(rest, vec![PageId(8), PageId(7), PageId(4), PageId(2), PageId(10)]);
+        drop(disk);
+
+        // -- 没有格式版本的旧文件拒绝打开, 而不是把每个page都报成损坏
+        let mut bytes = std::fs::read(striped.path()).unwrap();
+        bytes[28..32].fill(0);
+        std::fs::write(striped.path(), &bytes).unwrap();
+        let err = QcFileDisk::open(striped.path(), 0).err().unwrap();
+        println!("open old file: {err}");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version 0"));
+    }
+
+    #[test]
+    fn test_flush_all() {
+        let tmp = TmpDb::new("flush");
+
+        let bufpool = QcBuffpoolConfig::new(tmp.path())
+            .pool_size(4)
+            .build()
+            .unwrap();
+
+        let mut pids = Vec::new();
+        for v in ["x", "y", "z"] {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, v.to_string());
+            pids.push(wg.page_id());
+        }
+
+        // -- 仍被pin住的page也会被刷; 同一线程持有dirty page的读guard时不会死锁
+        let pinned = bufpool.fetch_page(pids[0]).unwrap();
+        let rg = bufpool.fetch_page_read(pids[1]).unwrap();
+        assert!(rg.is_dirty());
+        bufpool.flush_all().unwrap();
+        assert!(!rg.is_dirty());
+        assert!(!pinned.upgrade().unwrap().read().unwrap().is_dirty());
+        assert_eq!(bufpool.dirty_count(), 0);
+        drop(rg);
+        bufpool.unpin_page(pids[0]).unwrap();
+        drop(bufpool);
+
+        let bufpool = QcBuffpoolConfig::new(tmp.path())
+            .pool_size(4)
+            .build()
+            .unwrap();
+        for (pid, v) in pids.into_iter().zip(["x", "y", "z"]) {
+            assert_eq!(bufpool.fetch_page_read(pid).unwrap().obtain(1), Some(v.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_page_cleaner() {
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(4)
+            .cleaner(QcCleanerConfig {
+                interval: std::time::Duration::from_secs(60),
+                dirty_watermark: 0.5,
+            })
+            .build()
+            .unwrap();
+        assert!(bufpool.has_cleaner());
+
+        let mut pids = Vec::new();
+        for v in ["c1", "c2"] {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, v.to_string());
+            pids.push(wg.page_id());
+        }
+
+        // -- 达到水位后被唤醒, 不用等interval
+        let mut waited = 0;
+        while bufpool.dirty_count() > 0 && waited < 200 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            waited += 1;
+        }
+        assert_eq!(bufpool.dirty_count(), 0);
+
+        for pid in pids {
+            assert!(!bufpool.fetch_page_read(pid).unwrap().is_dirty());
+        }
+        drop(bufpool);
+    }
+
+    #[test]
+    fn test_buffpool_threads() {
+        fn is_send_sync<T: Send + Sync>() {}
+        is_send_sync::<QcBuffpool>();
+
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(8)
+            .build()
+            .unwrap();
+
+        let mut pids = Vec::new();
+        for i in 0..16 {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, format!("pg{i}"));
+            pids.push(wg.page_id());
+        }
+
+        // -- 16个page轮流挤8个frame, 各线程同时读写
+        std::thread::scope(|sc| {
+            for t in 0..4 {
+                let bufpool = &bufpool;
+                let pids = &pids;
+                sc.spawn(move || {
+                    for r in 0..200 {
+                        let idx = (t * 5 + r * 3) % pids.len();
+                        if r % 4 == 0 {
+                            let mut wg = bufpool.fetch_page_write(pids[idx]).unwrap();
+                            wg.save(0, format!("t{t}"));
+                        } else {
+                            let rg = bufpool.fetch_page_read(pids[idx]).unwrap();
+                            assert_eq!(rg.obtain(1), Some(format!("pg{idx}")));
+                        }
+                    }
+                });
+            }
+        });
+
+        bufpool.flush_all().unwrap();
+        for (i, &pid) in pids.iter().enumerate() {
+            let rg = bufpool.fetch_page_read(pid).unwrap();
+            assert_eq!(rg.obtain(1), Some(format!("pg{i}")));
+        }
+        println!("dirty after flush: {}", bufpool.dirty_count());
+        assert_eq!(bufpool.dirty_count(), 0);
+    }
+
+    #[test]
+    fn test_page_latch() {
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(4)
+            .build()
+            .unwrap();
+
+        let pid = {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, "shared".to_string());
+            wg.page_id()
+        };
+
+        // -- 多个读guard可同时持有同一page
+        let rg1 = bufpool.fetch_page_read(pid).unwrap();
+        let rg2 = bufpool.try_fetch_page_read(pid).unwrap();
+        assert_eq!(rg1.obtain(1), rg2.obtain(1));
+
+        let busy = bufpool.try_fetch_page_write(pid);
+        println!("try write under readers: {:?}", busy.as_ref().err());
+        assert!(matches!(busy, Err(QcBupoError::LatchBusy)));
+        drop(rg1);
+        drop(rg2);
+
+        let mut wg = bufpool.try_fetch_page_write(pid).unwrap();
+        wg.save(0, "excl".to_string());
+        assert!(matches!(bufpool.try_fetch_page_read(pid), Err(QcBupoError::LatchBusy)));
+        drop(wg);
+
+        // -- 失败的try不会遗留pin
+        bufpool.delete_page(pid).unwrap();
+    }
+
+    // -- 统计读盘次数, 读得慢一点让并发未命中重叠
+    struct SlowDisk {
+        disk: QcMemoryDisk,
+        delay: std::time::Duration,
+        reads: Arc<AtomicUsize>,
+        batches: Arc<AtomicUsize>,
+        advised: Arc<AtomicUsize>,
+    }
+
+    impl DiskManager for SlowDisk {
+        fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> std::io::Result<()> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(self.delay);
+            return self.disk.read_page(page_id, buf);
+        }
+        fn write_page(&self, page_id: PageId, buf: &[u8]) -> std::io::Result<()> {
+            return self.disk.write_page(page_id, buf);
+        }
+        fn write_pages(&self, reqs: &[(PageId, &[u8])]) -> std::io::Result<()> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            for (pid, buf) in reqs.iter() {
+                self.disk.write_page(*pid, buf)?;
+            }
+            return Ok(());
+        }
+        fn allocate_page(&self) -> std::io::Result<PageId> {
+            return self.disk.allocate_page();
+        }
+        fn deallocate_page(&self, page_id: PageId) -> std::io::Result<()> {
+            return self.disk.deallocate_page(page_id);
+        }
+        fn sync(&self) -> std::io::Result<()> {
+            return Ok(());
+        }
+        fn is_allocated(&self, page_id: PageId) -> bool {
+            return self.disk.is_allocated(page_id);
+        }
+        fn advise(&self, _first: PageId, count: usize) {
+            self.advised.fetch_add(count, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_single_flight() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let batches = Arc::new(AtomicUsize::new(0));
+        let disk = SlowDisk {
+            disk: QcMemoryDisk::new(),
+            delay: std::time::Duration::from_millis(50),
+            reads: Arc::clone(&reads),
+            batches: Arc::clone(&batches),
+            advised: Arc::new(AtomicUsize::new(0)),
+        };
+        let hot = disk.allocate_page().unwrap();
+        let mut pg = QcPager::new();
+        pg.save(1, "hot".to_string());
+        pg.set_checksum();
+        disk.write_page(hot, pg.buffer()).unwrap();
+
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(8)
+            .build_with_disk(Box::new(disk))
+            .unwrap();
+
+        // -- 同时未命中同一page, 只读一次盘
+        std::thread::scope(|sc| {
+            for _ in 0..4 {
+                sc.spawn(|| {
+                    let rg = bufpool.fetch_page_read(hot).unwrap();
+                    assert_eq!(rg.obtain(1), Some("hot".to_string()));
+                });
+            }
+        });
+        println!("reads: {}", reads.load(Ordering::SeqCst));
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+        for i in 0..4 {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, format!("b{i}"));
+        }
+        bufpool.flush_all().unwrap();
+        assert_eq!(bufpool.dirty_count(), 0);
+
+        // -- 按page id每2个一组: [hot, b0], [b1, b2], [b3]; 干净的hot占一个位置
+        println!("write batches: {}", batches.load(Ordering::SeqCst));
+        assert_eq!(batches.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let advised = Arc::new(AtomicUsize::new(0));
+        let disk = SlowDisk {
+            disk: QcMemoryDisk::new(),
+            delay: std::time::Duration::ZERO,
+            reads: Arc::clone(&reads),
+            batches: Arc::new(AtomicUsize::new(0)),
+            advised: Arc::clone(&advised),
+        };
+        for i in 0..12 {
+            let pid = disk.allocate_page().unwrap();
+            let mut pg = QcPager::new();
+            pg.save(1, format!("seq{i}"));
+            pg.set_checksum();
+            disk.write_page(pid, pg.buffer()).unwrap();
+        }
+
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(16)
+            .read_ahead(4)
+            .build_with_disk(Box::new(disk))
+            .unwrap();
+
+        assert!(bufpool.has_read_ahead());
+
+        // -- 预读在后台线程完成, 等到读盘次数达到预期
+        let wait_reads = |n: usize| {
+            for _ in 0..200 {
+                if reads.load(Ordering::SeqCst) >= n {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(reads.load(Ordering::SeqCst), n);
+        };
+
+        // -- 第3个相邻page之后预读4~7; 中间重新访问1不打断1~3这个流
+        for raw in [1, 2, 1, 3] {
+            bufpool.fetch_page_read(PageId(raw)).unwrap();
+        }
+        wait_reads(7);
+        for raw in 4..=6 {
+            let rg = bufpool.fetch_page_read(PageId(raw)).unwrap();
+            assert_eq!(rg.obtain(1), Some(format!("seq{}", raw - 1)));
+        }
+        println!("reads after scan: {}", reads.load(Ordering::SeqCst));
+
+        // -- 读到6时离已读入的末尾(7)不足半个窗口, 提前预读8~11, 之后的访问都命中
+        wait_reads(11);
+        for raw in 7..=8 {
+            bufpool.fetch_page_read(PageId(raw)).unwrap();
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 11);
+
+        // -- 每个预读窗口读盘前都先提示了存储层
+        assert_eq!(advised.load(Ordering::SeqCst), 8);
+
+        // -- 显式预读, 已在pool中的不重复读; 越过末尾的page id被拒绝
+        assert_eq!(bufpool.prefetch(PageId(10)..PageId(13)).unwrap(), 1);
+        assert_eq!(bufpool.prefetch(PageId(11)..PageId(13)).unwrap(), 0);
+        assert!(bufpool.prefetch(PageId(12)..PageId(14)).is_err());
+        let before = reads.load(Ordering::SeqCst);
+        bufpool.fetch_page_read(PageId(12)).unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), before);
+    }
+
+    #[test]
+    fn test_access_strategy() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let disk = SlowDisk {
+            disk: QcMemoryDisk::new(),
+            delay: std::time::Duration::ZERO,
+            reads: Arc::clone(&reads),
+            batches: Arc::new(AtomicUsize::new(0)),
+            advised: Arc::new(AtomicUsize::new(0)),
+        };
+        for i in 0..24 {
+            let pid = disk.allocate_page().unwrap();
+            let mut pg = QcPager::new();
+            pg.save(1, format!("row{i}"));
+            pg.set_checksum();
+            disk.write_page(pid, pg.buffer()).unwrap();
+        }
+
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(6)
+            .build_with_disk(Box::new(disk))
+            .unwrap();
+        for raw in 1..=4 {
+            bufpool.fetch_page_read(PageId(raw)).unwrap();
+        }
+
+        // -- 全表扫描只在2个frame中轮转
+        let mut scan = QcAccessStrategy::bulk_read(2);
+        for raw in 5..=24 {
+            let rg = bufpool.fetch_page_read_with(PageId(raw), &mut scan).unwrap();
+            assert_eq!(rg.obtain(1), Some(format!("row{}", raw - 1)));
+        }
+        let after_scan = reads.load(Ordering::SeqCst);
+        for raw in 1..=4 {
+            bufpool.fetch_page_read(PageId(raw)).unwrap();
+        }
+        println!("reads: {after_scan} -> {}", reads.load(Ordering::SeqCst));
+        assert_eq!(reads.load(Ordering::SeqCst), after_scan);
+
+        // -- 批量写入: 复用frame前写回, 最多留下ring大小的dirty page
+        let mut load = QcAccessStrategy::bulk_write(2);
+        let mut pids = Vec::new();
+        for i in 0..6 {
+            let mut wg = bufpool.new_page_with(&mut load).unwrap();
+            wg.save(1, format!("bulk{i}"));
+            pids.push(wg.page_id());
+        }
+        assert_eq!(bufpool.dirty_count(), 2);
+        for raw in 1..=4 {
+            bufpool.fetch_page_read(PageId(raw)).unwrap();
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), after_scan);
+
+        for (i, &pid) in pids.iter().enumerate() {
+            let rg = bufpool.fetch_page_read(pid).unwrap();
+            assert_eq!(rg.obtain(1), Some(format!("bulk{i}")));
+        }
+    }
+
+    #[test]
+    fn test_parallel_buffpool() {
+        let bufpool = QcBuffpoolConfig::memory()
+            .pool_size(2)
+            .build_parallel(3)
+            .unwrap();
+        assert_eq!(bufpool.instance_num(), 3);
+
+        // -- 连续的id轮流落在3个实例上, 每个实例2个frame
+        let mut guards = Vec::new();
+        for i in 0..6 {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, format!("p{i}"));
+            guards.push(wg);
+        }
+        let pids: Vec<PageId> = guards.iter().map(|wg| wg.page_id()).collect();
+        println!("pids: {:?}", pids);
+
+        // -- 全部pin住时先拿frame就失败, 不占用id
+        assert!(matches!(bufpool.new_page(), Err(QcBupoError::NoFreeFrame)));
+        drop(guards);
+        let next = bufpool.new_page().unwrap().page_id();
+        assert_eq!(next, PageId(7));
+        bufpool.delete_page(next).unwrap();
+
+        // -- 释放的id在它所在的实例上复用, 从末尾分配时跳过的id(8)留在free list
+        bufpool.delete_page(pids[4]).unwrap();
+        let mut guards: Vec<_> = (0..3).map(|_| bufpool.new_page().unwrap()).collect();
+        let got: Vec<PageId> = guards.iter().map(|wg| wg.page_id()).collect();
+        assert_eq!(got, vec![pids[4], PageId(9), PageId(7)]);
+        guards[0].save(1, "p4".to_string());
+        drop(guards);
+
+        std::thread::scope(|sc| {
+            for t in 0..3 {
+                let bufpool = &bufpool;
+                let pids = &pids;
+                sc.spawn(move || {
+                    for r in 0..60 {
+                        let idx = (t + r) % pids.len();
+                        let rg = bufpool.fetch_page_read(pids[idx]).unwrap();
+                        assert_eq!(rg.obtain(1), Some(format!("p{idx}")));
+                    }
+                });
+            }
+        });
+
+        bufpool.flush_all().unwrap();
+        assert_eq!(bufpool.dirty_count(), 0);
+    }
+
+    #[test]
+    fn test_page_checksum() {
+        assert_eq!(checksum::crc32c(b"123456789"), 0xE306_9283);
+
+        let tmp = TmpDb::new("checksum");
+        let path = tmp.path();
+        let pid = {
+            let bufpool = QcBuffpoolConfig::new(path).pool_size(4).build().unwrap();
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, "intact".to_string());
+            let pid = wg.page_id();
+            drop(wg);
+            bufpool.flush_all().unwrap();
+            pid
+        };
+
+        // -- 翻转一个data字节
+        let disk = QcFileDisk::open(path, 0).unwrap();
+        let mut buf = vec![0_u8; page::PAGE_SIZE];
+        disk.read_page(pid, &mut buf).unwrap();
+        assert!(checksum::verify_page(&buf));
+        buf[page::PAGE_SIZE - 1] ^= 0x40;
+        disk.write_page(pid, &buf).unwrap();
+        drop(disk);
+
+        let bufpool = QcBuffpoolConfig::new(path).pool_size(4).build().unwrap();
+        let err = bufpool.fetch_page_read(pid).err();
+        println!("fetch corrupted: {:?}", err);
+        assert!(matches!(err, Some(QcBupoError::Corruption(p)) if p == pid));
+
+        // -- 失败的读入不占用frame: 4个frame都还能同时pin住
+        let guards: Vec<_> = (0..4).map(|_| bufpool.new_page().unwrap()).collect();
+        drop(guards);
+        drop(bufpool);
+
+        // -- 分配后从未写过的page全为0, 不算损坏
+        let blank = QcFileDisk::open(path, 0).unwrap().allocate_page().unwrap();
+        let bufpool = QcBuffpoolConfig::new(path).pool_size(4).build().unwrap();
+        assert!(bufpool.fetch_page_read(blank).is_ok());
+    }
+
+    #[test]
+    fn test_double_write() {
+        let tmp = TmpDb::new("dwb");
+        let path = tmp.path();
+        let dwb = QcDoubleWriteDisk::dwb_path(path);
+
+        // -- 不sync: 模拟原地写入落盘前崩溃
+        let pid = {
+            let bufpool = QcBuffpoolConfig::new(path).double_write(true).sync_policy(QcSyncPolicy::Never).pool_size(4).build().unwrap();
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, "survive".to_string());
+            let pid = wg.page_id();
+            drop(wg);
+            bufpool.flush_all().unwrap();
+            pid
+        };
+        assert!(std::fs::metadata(&dwb).unwrap().len() > 0);
+
+        // -- 模拟原地写入只写了前半页
+        let disk = QcFileDisk::open(path, 0).unwrap();
+        let mut buf = vec![0_u8; page::PAGE_SIZE];
+        disk.read_page(pid, &mut buf).unwrap();
+        buf[page::PAGE_SIZE / 2..].fill(0);
+        disk.write_page(pid, &buf).unwrap();
+        drop(disk);
+
+        let dw = QcDoubleWriteDisk::open(Box::new(QcFileDisk::open(path, 0).unwrap()), &dwb).unwrap();
+        println!("restored pages: {}", dw.restored());
+        assert_eq!(dw.restored(), 1);
+        drop(dw);
+
+        let bufpool = QcBuffpoolConfig::new(path).double_write(true).pool_size(4).build().unwrap();
+        assert_eq!(bufpool.fetch_page_read(pid).unwrap().obtain(1), Some("survive".to_string()));
+        drop(bufpool);
+
+        // -- 释放的page不会被副本恢复, 重新打开后free list仍可用
+        let tmp = TmpDb::new("dwb_free");
+        let path = tmp.path();
+        {
+            let bufpool = QcBuffpoolConfig::new(path).double_write(true).pool_size(4).build().unwrap();
+            for v in ["a", "b", "c"] {
+                bufpool.new_page().unwrap().save(1, v.to_string());
+            }
+            bufpool.flush_all().unwrap();
+            // -- sync之后这一批副本已作废
+            assert_eq!(&std::fs::read(QcDoubleWriteDisk::dwb_path(path)).unwrap()[0..8], &[0; 8]);
+            bufpool.delete_page(PageId(2)).unwrap();
+            bufpool.delete_page(PageId(3)).unwrap();
+        }
+
+        let bufpool = QcBuffpoolConfig::new(path).double_write(true).pool_size(4).build().unwrap();
+        let pids: Vec<PageId> = (0..3).map(|_| bufpool.new_page().unwrap().page_id()).collect();
+        assert_eq!(pids, vec![PageId(3), PageId(2), PageId(4)]);
+        assert_eq!(bufpool.fetch_page_read(PageId(1)).unwrap().obtain(1), Some("a".to_string()));
+    }
+
+    #[test]
+    fn test_wal() {
+        let tmp = TmpDb::new("wal");
+        let path = tmp.path();
+        let wal_path = &tmp.wal();
+
+        let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(2).build().unwrap();
+        let log = Arc::clone(bufpool.log_manager().unwrap());
+
+        let begin = log.append(1, INVALID_LSN, QcLogBody::Begin).unwrap();
+        let mut wg = bufpool.new_page().unwrap();
+        let pid = wg.page_id();
+        let before = wg.buffer()[100..104].to_vec();
+        let lsn = log.append(1, begin, QcLogBody::Update {
+            page_id: pid,
+            offset: 100,
+            before,
+            after: b"wal!".to_vec(),
+        }).unwrap();
+        wg.mut_buffer()[100..104].copy_from_slice(b"wal!");
+        wg.set_page_lsn(lsn);
+        drop(wg);
+        assert!(log.flushed_lsn() <= lsn);
+
+        // -- 换出该page前日志必须先落盘
+        for _ in 0..2 {
+            drop(bufpool.new_page().unwrap());
+        }
+        println!("page lsn: {lsn}, flushed lsn: {}", log.flushed_lsn());
+        assert!(log.flushed_lsn() > lsn);
+
+        let rg = bufpool.fetch_page_read(pid).unwrap();
+        assert_eq!(&rg.buffer()[100..104], b"wal!");
+        assert_eq!(rg.page_lsn(), lsn);
+        drop(rg);
+
+        log.append(1, lsn, QcLogBody::Commit).unwrap();
+        drop(bufpool);
+        drop(log);
+
+        // -- 尾部写了一半的记录在打开时被丢弃
+        let end = std::fs::metadata(wal_path).unwrap().len();
+        let fd = std::fs::OpenOptions::new().append(true).open(wal_path).unwrap();
+        std::io::Write::write_all(&mut &fd, &[0, 0, 0, 64, 1, 2]).unwrap();
+        drop(fd);
+
+        let log = QcLogManager::open(wal_path).unwrap();
+        assert_eq!(log.next_lsn(), end);
+        let recs: Vec<QcLogRecord> = log.iter_from(INVALID_LSN).unwrap().map(|r| r.unwrap()).collect();
+        for rec in recs.iter() {
+            println!("{rec:?}");
+        }
+        assert_eq!(recs.len(), 2);
+        assert_eq!(recs[1].lsn, lsn);
+        assert_eq!(recs[1].prev_lsn, begin);
+        assert_eq!(recs[1].body.page_id(), Some(pid));
+        assert_eq!(log.read(lsn).unwrap(), Some(recs[1].clone()));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let tmp = TmpDb::new("recovery");
+        let path = tmp.path();
+        let wal_path = &tmp.wal();
+
+        // -- 记一条Update再改page
+        fn update(bufpool: &QcBuffpool, txn: u64, prev: u64, pid: PageId, offset: u16, after: &[u8]) -> u64 {
+            let log = bufpool.log_manager().unwrap();
+            let mut wg = bufpool.fetch_page_write(pid).unwrap();
+            let off = offset as usize;
+            let before = wg.buffer()[off..off + after.len()].to_vec();
+            let lsn = log.append(txn, prev, QcLogBody::Update { page_id: pid, offset, before, after: after.to_vec() }).unwrap();
+            wg.mut_buffer()[off..off + after.len()].copy_from_slice(after);
+            wg.set_page_lsn(lsn);
+            return lsn;
+        }
+
+        let (pa, pb) = {
+            let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+            assert_eq!(bufpool.recovery_report().unwrap().redone, 0);
+            let log = Arc::clone(bufpool.log_manager().unwrap());
+            let pa = bufpool.new_page().unwrap().page_id();
+            let pb = bufpool.new_page().unwrap().page_id();
+
+            // -- 事务1已提交, page没有写回
+            let lsn = log.append(1, INVALID_LSN, QcLogBody::Begin).unwrap();
+            let lsn = update(&bufpool, 1, lsn, pa, 200, b"AAAA");
+            let lsn = log.append(1, lsn, QcLogBody::Commit).unwrap();
+            log.flush_to(lsn).unwrap();
+
+            // -- 事务2未提交, page已写回
+            let lsn = log.append(2, INVALID_LSN, QcLogBody::Begin).unwrap();
+            update(&bufpool, 2, lsn, pb, 200, b"BBBB");
+            bufpool.flush_page(pb).unwrap();
+
+            // -- 事务3的日志还在buffer中, 随崩溃丢失
+            let lsn = log.append(3, INVALID_LSN, QcLogBody::Begin).unwrap();
+            update(&bufpool, 3, lsn, pa, 300, b"CCCC");
+
+            // -- 不写回直接丢弃pool, 模拟崩溃
+            (pa, pb)
+        };
+
+        for round in 0..2 {
+            let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+            let report = bufpool.recovery_report().unwrap().clone();
+            println!("round {round}: {report:?}");
+            if round == 0 {
+                assert_eq!(report.losers, vec![2]);
+                assert_eq!(report.undone, 1);
+            } else {
+                // -- 事务2已写过End, 只需重做
+                assert!(report.losers.is_empty());
+                assert_eq!(report.undone, 0);
+            }
+            assert!(report.redone > 0);
+            assert_eq!(report.max_txn, 2);
+
+            let ra = bufpool.fetch_page_read(pa).unwrap();
+            assert_eq!(&ra.buffer()[200..204], b"AAAA");
+            assert_eq!(&ra.buffer()[300..304], &[0; 4]);
+            drop(ra);
+            let rb = bufpool.fetch_page_read(pb).unwrap();
+            assert_eq!(&rb.buffer()[200..204], &[0; 4]);
+        }
+
+        // -- 已提交并写回的page被删除, 重开时不能把修改重做到空闲page上
+        let pc = {
+            let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+            let tm = QcTransactionManager::new(Arc::clone(bufpool.log_manager().unwrap()));
+            let pc = bufpool.new_page().unwrap().page_id();
+            let txn = tm.begin().unwrap();
+            let mut wg = bufpool.fetch_page_write(pc).unwrap();
+            tm.modify(&txn, &mut wg, |pg| pg.save(0, "freed".to_string())).unwrap();
+            drop(wg);
+            tm.commit(txn).unwrap();
+            bufpool.flush_all().unwrap();
+            bufpool.delete_page(pc).unwrap();
+            pc
+        };
+
+        for round in 0..2 {
+            let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+            let report = bufpool.recovery_report().unwrap();
+            println!("freed round {round}: {report:?}");
+            assert_eq!(report.redone, 0);
+            assert!(report.losers.is_empty());
+            bufpool.flush_all().unwrap();
+            if round == 1 {
+                let wg = bufpool.new_page().unwrap();
+                assert_eq!(wg.page_id(), pc);
+                assert_eq!(wg.obtain(0), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let tmp = TmpDb::new("checkpoint");
+        let path = tmp.path();
+        let wal_path = &tmp.wal();
+
+        fn update(bufpool: &QcBuffpool, txn: u64, prev: u64, pid: PageId, after: &[u8]) -> u64 {
+            let log = bufpool.log_manager().unwrap();
+            let mut wg = bufpool.fetch_page_write(pid).unwrap();
+            let before = wg.buffer()[200..200 + after.len()].to_vec();
+            let lsn = log.append(txn, prev, QcLogBody::Update { page_id: pid, offset: 200, before, after: after.to_vec() }).unwrap();
+            wg.mut_buffer()[200..200 + after.len()].copy_from_slice(after);
+            wg.set_page_lsn(lsn);
+            return lsn;
+        }
+
+        let (pa, pb, pc, ckpt) = {
+            let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+            let log = Arc::clone(bufpool.log_manager().unwrap());
+            let pa = bufpool.new_page().unwrap().page_id();
+            let pb = bufpool.new_page().unwrap().page_id();
+            let pc = bufpool.new_page().unwrap().page_id();
+
+            // -- 事务1已结束但page没有写回, 事务3在checkpoint时还在进行
+            let lsn = log.append(1, INVALID_LSN, QcLogBody::Begin).unwrap();
+            let lsn = update(&bufpool, 1, lsn, pa, b"AAAA");
+            let lsn = log.append(1, lsn, QcLogBody::Commit).unwrap();
+            log.append(1, lsn, QcLogBody::End).unwrap();
+            let lsn = log.append(3, INVALID_LSN, QcLogBody::Begin).unwrap();
+            let lsn3 = update(&bufpool, 3, lsn, pc, b"CCCC");
+            bufpool.flush_page(pb).unwrap();
+
+            let ckpt = bufpool.checkpoint().unwrap();
+            assert_eq!(QcFileDisk::open(path, 0).unwrap().checkpoint_lsn().unwrap(), ckpt);
+
+            let recs: Vec<QcLogRecord> = log.iter_from(ckpt).unwrap().map(|r| r.unwrap()).collect();
+            println!("{:?}", recs[1]);
+            let QcLogBody::CheckpointEnd { dpt, att } = &recs[1].body else {
+                panic!("expect checkpoint end");
+            };
+            assert!(dpt.iter().any(|&(pid, rec_lsn)| pid == pa && rec_lsn < ckpt));
+            assert!(dpt.iter().any(|&(pid, _)| pid == pc));
+            assert!(!dpt.iter().any(|&(pid, _)| pid == pb));
+            assert_eq!(att.len(), 1);
+            assert_eq!((att[0].txn_id, att[0].last_lsn, att[0].committed), (3, lsn3, false));
+
+            // -- checkpoint之后事务2未提交
+            let lsn = log.append(2, INVALID_LSN, QcLogBody::Begin).unwrap();
+            update(&bufpool, 2, lsn, pb, b"BBBB");
+            log.flush().unwrap();
+
+            (pa, pb, pc, ckpt)
+        };
+
+        // -- 按日志量触发的后台checkpoint
+        let bufpool = QcBuffpoolConfig::new(path)
+            .wal(wal_path)
+            .pool_size(4)
+            .checkpoint(QcCheckpointConfig { interval: None, log_bytes: Some(1) })
+            .build()
+            .unwrap();
+        let report = bufpool.recovery_report().unwrap();
+        println!("{report:?}");
+        assert_eq!(report.losers, vec![2, 3]);
+        assert_eq!(report.undone, 2);
+        assert_eq!(&bufpool.fetch_page_read(pa).unwrap().buffer()[200..204], b"AAAA");
+        assert_eq!(&bufpool.fetch_page_read(pb).unwrap().buffer()[200..204], &[0; 4]);
+        assert_eq!(&bufpool.fetch_page_read(pc).unwrap().buffer()[200..204], &[0; 4]);
+
+        let log = Arc::clone(bufpool.log_manager().unwrap());
+        let lsn = log.append(4, INVALID_LSN, QcLogBody::Begin).unwrap();
+        let mut auto = INVALID_LSN;
+        for _ in 0..40 {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            auto = QcFileDisk::open(path, 0).unwrap().checkpoint_lsn().unwrap();
+            if auto > lsn {
+                break;
+            }
+        }
+        println!("manual checkpoint: {ckpt}, background checkpoint: {auto}");
+        assert!(auto > lsn);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let tmp = TmpDb::new("txn");
+        let path = tmp.path();
+        let wal_path = &tmp.wal();
+
+        let (pa, pb) = {
+            let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+            let log = Arc::clone(bufpool.log_manager().unwrap());
+            let tm = QcTransactionManager::new(Arc::clone(&log));
+            let pa = bufpool.new_page().unwrap().page_id();
+            let pb = bufpool.new_page().unwrap().page_id();
+
+            let t1 = tm.begin().unwrap();
+            let mut wg = bufpool.fetch_page_write(pa).unwrap();
+            tm.modify(&t1, &mut wg, |pg| pg.save(0, "keep".to_string())).unwrap();
+            drop(wg);
+            let lsn = log.next_lsn();
+            tm.commit(t1).unwrap();
+            assert!(log.flushed_lsn() > lsn);
+
+            let t2 = tm.begin().unwrap();
+            assert!(t2.id() > 1);
+            let mut wg = bufpool.fetch_page_write(pb).unwrap();
+            tm.modify(&t2, &mut wg, |pg| pg.save(0, "gone".to_string())).unwrap();
+            drop(wg);
+            let mut wg = bufpool.fetch_page_write(pa).unwrap();
+            tm.modify(&t2, &mut wg, |pg| pg.mut_buffer()[500..504].copy_from_slice(b"temp")).unwrap();
+            assert_eq!(wg.obtain(0), Some("keep".to_string()));
+            drop(wg);
+            assert_eq!(tm.write_set(&t2), vec![pa, pb]);
+
+            tm.abort(t2, |pid| bufpool.fetch_page_write(pid)).unwrap();
+            assert_eq!(tm.active_count(), 0);
+            let ra = bufpool.fetch_page_read(pa).unwrap();
+            assert_eq!(ra.obtain(0), Some("keep".to_string()));
+            assert_eq!(&ra.buffer()[500..504], &[0; 4]);
+            drop(ra);
+            assert_eq!(bufpool.fetch_page_read(pb).unwrap().obtain(0), None);
+
+            // -- 未提交的事务随崩溃回滚
+            let t3 = tm.begin().unwrap();
+            let mut wg = bufpool.fetch_page_write(pb).unwrap();
+            tm.modify(&t3, &mut wg, |pg| pg.save(0, "lost".to_string())).unwrap();
+            drop(wg);
+
+            // -- page锁被t3持有, 其他事务的修改被拒绝且不执行
+            let t4 = tm.begin().unwrap();
+            let mut wg = bufpool.fetch_page_write(pb).unwrap();
+            let err = tm.modify(&t4, &mut wg, |pg| pg.save(0, "other".to_string())).err();
+            println!("{err:?}");
+            assert!(matches!(err, Some(QcBupoError::TxnConflict(pid)) if pid == pb));
+            assert_eq!(wg.obtain(0), Some("lost".to_string()));
+            drop(wg);
+            let mut wg = bufpool.fetch_page_write(pa).unwrap();
+            tm.modify(&t4, &mut wg, |pg| pg.mut_buffer()[600..602].copy_from_slice(b"t4")).unwrap();
+            drop(wg);
+            tm.abort(t4, |pid| bufpool.fetch_page_write(pid)).unwrap();
+
+            // -- t4回滚后释放了pa的锁
+            let t5 = tm.begin().unwrap();
+            let mut wg = bufpool.fetch_page_write(pa).unwrap();
+            tm.modify(&t5, &mut wg, |pg| pg.mut_buffer()[600..602].copy_from_slice(b"t5")).unwrap();
+            drop(wg);
+            tm.commit(t5).unwrap();
+            bufpool.flush_all().unwrap();
+
+            (pa, pb)
+        };
+
+        let bufpool = QcBuffpoolConfig::new(path).wal(wal_path).pool_size(4).build().unwrap();
+        let report = bufpool.recovery_report().unwrap();
+        println!("{report:?}");
+        assert_eq!(report.losers, vec![3]);
+        assert_eq!(bufpool.fetch_page_read(pa).unwrap().obtain(0), Some("keep".to_string()));
+        assert_eq!(&bufpool.fetch_page_read(pa).unwrap().buffer()[600..602], b"t5");
+        assert_eq!(bufpool.fetch_page_read(pb).unwrap().obtain(0), None);
+
+        let tm = QcTransactionManager::new(Arc::clone(bufpool.log_manager().unwrap()));
+        assert_eq!(tm.begin().unwrap().id(), 6);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn test_uring_disk() {
+        use uring::QcUringDisk;
+
+        let tmp = TmpDb::new("uring");
+        let disk = QcUringDisk::open(tmp.path(), 4).unwrap();
+        let pids: Vec<PageId> = (0..3).map(|_| disk.allocate_page().unwrap()).collect();
+
+        let bufs: Vec<Vec<u8>> = pids.iter()
+            .map(|pid| vec![pid.0 as u8; page::PAGE_SIZE])
+            .collect();
+        let reqs: Vec<(PageId, &[u8])> = pids.iter().copied().zip(bufs.iter().map(|b| &b[..])).collect();
+        disk.write_pages(&reqs).unwrap();
+
+        let mut outs = vec![vec![0_u8; page::PAGE_SIZE]; 3];
+        let mut reqs: Vec<(PageId, &mut [u8])> = pids.iter().copied().zip(outs.iter_mut().map(|b| &mut b[..])).collect();
+        disk.read_pages(&mut reqs).unwrap();
+        assert_eq!(outs, bufs);
+
+        // -- 线程数多于ring数, 共用一个ring的请求各自取回结果
+        std::thread::scope(|sc| {
+            for _ in 0..8 {
+                sc.spawn(|| {
+                    for _ in 0..20 {
+                        let mut outs = vec![vec![0_u8; page::PAGE_SIZE]; 3];
+                        let mut reqs: Vec<(PageId, &mut [u8])> = pids.iter().copied().zip(outs.iter_mut().map(|b| &mut b[..])).collect();
+                        disk.read_pages(&mut reqs).unwrap();
+                        assert_eq!(outs, bufs);
+                    }
+                });
+            }
+        });
+
+        // -- 文件末尾之外读出0
+        let mut far = vec![1_u8; page::PAGE_SIZE];
+        disk.read_page(PageId(1000), &mut far).unwrap();
+        assert!(far.iter().all(|&b| b == 0));
+        drop(disk);
+
+        let bufpool = QcBuffpoolConfig::uring(tmp.path()).pool_size(4).build().unwrap();
+        let pid = {
+            let mut wg = bufpool.new_page().unwrap();
+            wg.save(1, "ring".to_string());
+            wg.page_id()
+        };
+        bufpool.flush_all().unwrap();
+        drop(bufpool);
+
+        let bufpool = QcBuffpoolConfig::uring(tmp.path()).pool_size(4).build().unwrap();
+        assert_eq!(bufpool.fetch_page_read(pid).unwrap().obtain(1), Some("ring".to_string()));
     }
 }
@@ -0,0 +1,105 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::replacer::{FrameId, Replacer};
+use crate::trace::PageId;
+
+#[derive(Debug)]
+struct QcLruKNode {
+    history: VecDeque<u64>,     // 最近k次访问时间戳, 最旧在前
+    evictable: bool,
+}
+
+// -- LRU-K: 淘汰 backward k-distance 最大的 frame
+//      访问不足k次的视为无穷远, 之间按最早访问时间淘汰
+#[derive(Debug)]
+pub struct QcLruKReplacer {
+    k: usize,
+    capacity: usize,
+    timestamp: u64,
+    evictable_num: usize,
+    nodes: HashMap<FrameId, QcLruKNode>,
+}
+
+impl QcLruKReplacer {
+    pub fn new(capacity: usize, k: usize) -> Self {
+        assert!(k > 0, "k must be positive");
+
+        QcLruKReplacer {
+            k,
+            capacity,
+            timestamp: 0,
+            evictable_num: 0,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn report(&self) {
+        for (fid, nd) in self.nodes.iter() {
+            println!("\t{fid}: {:?}", nd);
+        }
+    }
+}
+
+impl Replacer for QcLruKReplacer {
+    fn record_access(&mut self, frame_id: FrameId, _page_id: PageId) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        self.timestamp += 1;
+        let nd = self.nodes.entry(frame_id).or_insert(QcLruKNode {
+            history: VecDeque::with_capacity(self.k),
+            evictable: false,
+        });
+
+        if nd.history.len() >= self.k {
+            nd.history.pop_front();
+        }
+        nd.history.push_back(self.timestamp);
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        let Some(nd) = self.nodes.get_mut(&frame_id) else {
+            return;
+        };
+
+        if nd.evictable != evictable {
+            nd.evictable = evictable;
+            if evictable {
+                self.evictable_num += 1;
+            } else {
+                self.evictable_num -= 1;
+            }
+        }
+    }
+
+    fn evict(&mut self) -> Option<FrameId> {
+        // -- (是否满k次, 第k近的访问时间) 越小越优先淘汰
+        let victim = self.nodes
+            .iter()
+            .filter(|(_, nd)| nd.evictable)
+            .min_by_key(|(_, nd)| (nd.history.len() >= self.k, nd.history.front().copied()))
+            .map(|(&fid, _)| fid);
+
+        let Some(fid) = victim else {
+            return None;
+        };
+
+        self.remove(fid);
+        return Some(fid);
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        let Some(nd) = self.nodes.remove(&frame_id) else {
+            return;
+        };
+
+        if nd.evictable {
+            self.evictable_num -= 1;
+        }
+    }
+
+    fn size(&self) -> usize {
+        return self.evictable_num;
+    }
+}
@@ -1,7 +1,13 @@
-#[derive(Debug, Clone)]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{checksum, wal::Lsn};
+
+pub const PAGE_SIZE: usize = 4096;
+
+#[derive(Debug)]
 pub struct QcPager {
-    dirty: bool,
-    data: [u8; 4096],
+    dirty: AtomicBool,
+    data: [u8; PAGE_SIZE],
 }
 
 impl QcPager {
@@ -11,12 +17,12 @@ impl QcPager {
 
     pub fn new() -> Self {
         let mut pg = QcPager {
-            dirty: false,
-            data: [0_u8; 4096],
+            dirty: AtomicBool::new(false),
+            data: [0_u8; PAGE_SIZE],
         };
 
         pg.set_slot_len(0);
-        pg.set_slot_pointer(16); // -- slot start offset
+        pg.set_slot_pointer(24); // -- slot start offset, [16~23]为page LSN
         pg.set_data_pointer(4095); // -- data end offset
 
         return pg;
@@ -31,6 +37,25 @@ impl QcPager {
         return self.data.as_mut_slice();
     }
 
+    // -- 写盘前填入[0~3]的校验和
+    pub fn set_checksum(&mut self) {
+        checksum::stamp_page(&mut self.data);
+    }
+
+    pub fn checksum_ok(&self) -> bool {
+        checksum::verify_page(&self.data)
+    }
+
+    // -- 最后一次修改本page的日志记录
+    pub fn page_lsn(&self) -> Lsn {
+        u64::from_be_bytes(self.data[16..24].try_into().unwrap())
+    }
+
+    pub fn set_page_lsn(&mut self, lsn: Lsn) {
+        self.op_dirty();
+        self.data[16..24].copy_from_slice(&lsn.to_be_bytes());
+    }
+
     pub fn is_valiable(&self) -> bool {
         self.get_slot_len() > 0
     }
@@ -127,12 +152,12 @@ impl QcPager {
             .collect();
 
         // println!("(binary_search) slot_number_list: {:?}", block_list);
-        if block_list.len() == 0 {
+        if block_list.is_empty() {
             return (0, None);
         }
 
         let mut pl = 0;
-        let mut pr = if block_list.len() > 0 {
+        let mut pr = if !block_list.is_empty() {
             block_list.len() - 1
         } else {
             0
@@ -174,14 +199,15 @@ impl QcPager {
     }
 
     // -- dirty control
+    //      写回只持有读latch, 拷贝后清除或写失败后重新标记不需要可变引用
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.dirty.load(Ordering::Relaxed)
     }
-    pub fn op_dirty(&mut self) {
-        self.dirty = true;
+    pub fn op_dirty(&self) {
+        self.dirty.store(true, Ordering::Relaxed);
     }
-    pub fn op_clear(&mut self) {
-        self.dirty = false;
+    pub fn op_clear(&self) {
+        self.dirty.store(false, Ordering::Relaxed);
     }
 
     // -- about slot
@@ -216,8 +242,8 @@ impl QcPager {
 
         let inner: Vec<u8> = u32::to_be_bytes(k)
             .into_iter()
-            .chain(u16::to_be_bytes(dstart).into_iter())
-            .chain(u16::to_be_bytes(vlen).into_iter())
+            .chain(u16::to_be_bytes(dstart))
+            .chain(u16::to_be_bytes(vlen))
             .collect();
 
         mslot.clone_from_slice(&inner);
@@ -260,3 +286,18 @@ impl QcPager {
         self.get_data_pointer() - self.get_slot_pointer() - self.get_slot_len()
     }
 }
+
+impl Clone for QcPager {
+    fn clone(&self) -> Self {
+        QcPager {
+            dirty: AtomicBool::new(self.is_dirty()),
+            data: self.data,
+        }
+    }
+}
+
+impl Default for QcPager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
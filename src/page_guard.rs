@@ -0,0 +1,99 @@
+use std::{ops::{Deref, DerefMut}, sync::{RwLockReadGuard, RwLockWriteGuard}};
+
+use crate::{buffpool::QcBuffpool, page::QcPager, replacer::FrameId, trace::PageId};
+
+// -- 读guard: 创建时已pin住并持有frame读锁, 可与其它读guard共存; drop时先释放锁再unpin
+pub struct ReadPageGuard<'a> {
+    pool: &'a QcBuffpool,
+    page_id: PageId,
+    latch: Option<RwLockReadGuard<'a, QcPager>>,
+}
+
+impl<'a> ReadPageGuard<'a> {
+    pub(crate) fn new(pool: &'a QcBuffpool, page_id: PageId, latch: RwLockReadGuard<'a, QcPager>) -> Self {
+        ReadPageGuard {
+            pool,
+            page_id,
+            latch: Some(latch),
+        }
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+}
+
+impl Deref for ReadPageGuard<'_> {
+    type Target = QcPager;
+
+    fn deref(&self) -> &QcPager {
+        self.latch.as_ref().unwrap()
+    }
+}
+
+impl Drop for ReadPageGuard<'_> {
+    fn drop(&mut self) {
+        drop(self.latch.take());
+        self.pool.unpin_page(self.page_id);
+    }
+}
+
+// -- 写guard: 独占frame, 可变访问时标记dirty
+//      修改page的日志需在持有guard期间追加, 脏页表的rec lsn在创建guard时取得
+pub struct WritePageGuard<'a> {
+    pool: &'a QcBuffpool,
+    page_id: PageId,
+    frame_id: FrameId,
+    latch: Option<RwLockWriteGuard<'a, QcPager>>,
+    rec_lsn_set: bool,
+}
+
+impl<'a> WritePageGuard<'a> {
+    pub(crate) fn new(pool: &'a QcBuffpool, page_id: PageId, frame_id: FrameId, latch: RwLockWriteGuard<'a, QcPager>) -> Self {
+        let rec_lsn_set = pool.begin_write(frame_id);
+        WritePageGuard {
+            pool,
+            page_id,
+            frame_id,
+            latch: Some(latch),
+            rec_lsn_set,
+        }
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+}
+
+impl Deref for WritePageGuard<'_> {
+    type Target = QcPager;
+
+    fn deref(&self) -> &QcPager {
+        self.latch.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for WritePageGuard<'_> {
+    fn deref_mut(&mut self) -> &mut QcPager {
+        let pg = self.latch.as_mut().unwrap();
+        pg.op_dirty();
+        pg
+    }
+}
+
+impl Drop for WritePageGuard<'_> {
+    fn drop(&mut self) {
+        let latch = self.latch.take().unwrap();
+        let dirty = latch.is_dirty();
+        if !dirty && self.rec_lsn_set {
+            self.pool.cancel_write(self.frame_id);
+        }
+        drop(latch);
+
+        // -- 仍被pin住, frame不会被换掉
+        if dirty {
+            self.pool.mark_dirty(self.frame_id);
+        }
+        self.pool.unpin_page(self.page_id);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{collections::HashMap, sync::{Condvar, Mutex, MutexGuard}};
+
+use crate::{replacer::FrameId, trace::PageId};
+
+#[derive(Debug)]
+pub(crate) struct QcBuffItem {
+    pub(crate) frame_id: FrameId,
+    pub(crate) ref_num: i32,
+    // -- 正在从磁盘读入, 其它线程等待而不重复读
+    pub(crate) loading: bool,
+}
+
+impl QcBuffItem {
+    pub fn new(frame_id: FrameId, ref_num: i32) -> Self {
+        return QcBuffItem{
+            frame_id,
+            ref_num,
+            loading: false,
+        };
+    }
+
+    pub fn loading(frame_id: FrameId) -> Self {
+        return QcBuffItem{
+            frame_id,
+            ref_num: 1,
+            loading: true,
+        };
+    }
+}
+
+pub(crate) type QcShard = HashMap<PageId, QcBuffItem>;
+
+// -- 分片的page table, 不同分片的page可以并发pin/unpin
+pub(crate) struct QcPageTable {
+    shards: Vec<Mutex<QcShard>>,
+    loaded: Vec<Condvar>,
+}
+
+impl QcPageTable {
+    const SHARD_NUM: u64 = 16;
+
+    pub fn new() -> Self {
+        QcPageTable {
+            shards: (0..Self::SHARD_NUM).map(|_| Mutex::new(HashMap::new())).collect(),
+            loaded: (0..Self::SHARD_NUM).map(|_| Condvar::new()).collect(),
+        }
+    }
+
+    fn index(page_id: PageId) -> usize {
+        (page_id.0 % Self::SHARD_NUM) as usize
+    }
+
+    pub fn shard(&self, page_id: PageId) -> MutexGuard<'_, QcShard> {
+        return self.shards[Self::index(page_id)].lock().unwrap();
+    }
+
+    pub fn shards(&self) -> impl Iterator<Item = MutexGuard<'_, QcShard>> {
+        self.shards.iter().map(|sd| sd.lock().unwrap())
+    }
+
+    // -- 等到page读入完成或读入失败被移除
+    pub fn wait_loaded<'a>(&'a self, shard: MutexGuard<'a, QcShard>, page_id: PageId) -> MutexGuard<'a, QcShard> {
+        return self.loaded[Self::index(page_id)]
+            .wait_while(shard, |sd| sd.get(&page_id).is_some_and(|pgi| pgi.loading))
+            .unwrap();
+    }
+
+    pub fn notify_loaded(&self, page_id: PageId) {
+        self.loaded[Self::index(page_id)].notify_all();
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc, RwLock, Weak};
+
+use crate::{buffpool::QcBuffpool, checkpoint::{self, QcCheckpointer}, config::{QcBuffpoolConfig, QcSyncPolicy}, disk::DiskManager, error::QcBupoError, page::QcPager, page_guard::{ReadPageGuard, WritePageGuard}, recovery::{QcRecovery, QcRecoveryReport}, trace::PageId, wal::{Lsn, QcLogManager}};
+
+// -- 多个独立的QcBuffpool实例, 按 page_id % n 分配到实例
+//      各实例有自己的frame/table/replacer, 只共享存储
+pub struct QcParallelBuffpool {
+    instances: Vec<QcBuffpool>,
+    storage: Arc<dyn DiskManager>,
+    log: Option<Arc<QcLogManager>>,
+    sync: QcSyncPolicy,
+    recovery: Option<QcRecoveryReport>,
+    checkpointer: Option<QcCheckpointer>,
+    next: AtomicUsize,                  // 下一个分配新page的实例, 只是提示
+}
+
+impl QcParallelBuffpool {
+    pub(crate) fn open(config: &QcBuffpoolConfig, instances: usize, storage: Arc<dyn DiskManager>, log: Option<Arc<QcLogManager>>) -> Result<Self, QcBupoError> {
+        assert!(instances > 0, "instance number must be positive");
+
+        let mut pools = Vec::with_capacity(instances);
+        for _ in 0..instances {
+            let replacer = config.replacer.build(config.pool_size);
+            pools.push(QcBuffpool::open(config, replacer, Arc::clone(&storage), log.clone())?);
+        }
+
+        return Ok(QcParallelBuffpool {
+            instances: pools,
+            storage,
+            log,
+            sync: config.sync,
+            recovery: None,
+            checkpointer: None,
+            // -- page id从1开始, 新文件上依次分配的id正好轮流落在各实例
+            next: AtomicUsize::new(1),
+        });
+    }
+
+    // -- 日志由各实例共享, 恢复与checkpoint都在整个pool上做
+    pub(crate) fn recover_on_open(mut self, config: &QcBuffpoolConfig) -> Result<Self, QcBupoError> {
+        let Some(log) = self.log.clone() else {
+            return Ok(self);
+        };
+
+        let ckpt = self.storage.checkpoint_lsn()?;
+        let report = QcRecovery::new(&log, ckpt).run(|pid| self.fetch_page_write(pid))?;
+        self.recovery = Some(report);
+
+        self.checkpointer = config.checkpoint
+            .map(|cc| QcCheckpointer::spawn(self.instances.iter().map(|pool| Arc::clone(pool.inner())).collect(), cc));
+
+        return Ok(self);
+    }
+
+    pub fn checkpoint(&self) -> Result<Lsn, QcBupoError> {
+        let pools: Vec<_> = self.instances.iter().map(|pool| Arc::clone(pool.inner())).collect();
+        return checkpoint::checkpoint(&pools);
+    }
+
+    pub fn recovery_report(&self) -> Option<&QcRecoveryReport> {
+        self.recovery.as_ref()
+    }
+
+    pub fn log_manager(&self) -> Option<&Arc<QcLogManager>> {
+        self.log.as_ref()
+    }
+
+    fn instance(&self, page_id: PageId) -> &QcBuffpool {
+        &self.instances[(page_id.0 % self.instances.len() as u64) as usize]
+    }
+
+    pub fn instance_num(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn fetch_page(&self, page_id: PageId) -> Result<Weak<RwLock<QcPager>>, QcBupoError> {
+        return self.instance(page_id).fetch_page(page_id);
+    }
+
+    pub fn fetch_page_read(&self, page_id: PageId) -> Result<ReadPageGuard<'_>, QcBupoError> {
+        return self.instance(page_id).fetch_page_read(page_id);
+    }
+
+    pub fn fetch_page_write(&self, page_id: PageId) -> Result<WritePageGuard<'_>, QcBupoError> {
+        return self.instance(page_id).fetch_page_write(page_id);
+    }
+
+    pub fn try_fetch_page_read(&self, page_id: PageId) -> Result<ReadPageGuard<'_>, QcBupoError> {
+        return self.instance(page_id).try_fetch_page_read(page_id);
+    }
+
+    pub fn try_fetch_page_write(&self, page_id: PageId) -> Result<WritePageGuard<'_>, QcBupoError> {
+        return self.instance(page_id).try_fetch_page_write(page_id);
+    }
+
+    // -- 分配新page: 按轮转选实例, 由存储层分配落在该实例上的id
+    //      实例先拿到frame再分配id, 没有空frame时换下一个实例, 失败不占用id
+    pub fn new_page(&self) -> Result<WritePageGuard<'_>, QcBupoError> {
+        let num = self.instances.len();
+        let start = self.next.load(Ordering::Relaxed);
+        for i in 0..num {
+            let idx = (start + i) % num;
+            match self.instances[idx].new_page_striped(num as u64, idx as u64) {
+                Err(QcBupoError::NoFreeFrame) => continue,
+                Ok(guard) => {
+                    self.next.store(idx + 1, Ordering::Relaxed);
+                    return Ok(guard);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        return Err(QcBupoError::NoFreeFrame);
+    }
+
+    pub fn delete_page(&self, page_id: PageId) -> Result<(), QcBupoError> {
+        return self.instance(page_id).delete_page(page_id);
+    }
+
+    pub fn unpin_page(&self, page_id: PageId) -> Option<()> {
+        return self.instance(page_id).unpin_page(page_id);
+    }
+
+    pub fn flush_page(&self, page_id: PageId) -> Result<(), QcBupoError> {
+        return self.instance(page_id).flush_page(page_id);
+    }
+
+    // -- 各实例写回后统一sync一次
+    pub fn flush_all(&self) -> Result<(), QcBupoError> {
+        let mut written = 0;
+        for pool in self.instances.iter() {
+            written += pool.write_all()?;
+        }
+
+        if written > 0 && self.sync != QcSyncPolicy::Never {
+            self.storage.sync()?;
+        }
+
+        return Ok(());
+    }
+
+    pub fn dirty_count(&self) -> usize {
+        self.instances.iter().map(|pool| pool.dirty_count()).sum()
+    }
+}
@@ -0,0 +1,44 @@
+use std::{sync::Arc, thread::{self, JoinHandle}, time::Duration};
+
+use crate::buffpool::QcPoolInner;
+
+// -- 后台预读线程: 发现顺序访问时fetch只登记请求, 由它读入下一个窗口
+//      随 QcBuffpool 一起销毁
+pub(crate) struct QcReadAhead {
+    inner: Arc<QcPoolInner>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl QcReadAhead {
+    // -- 没有被唤醒时多久检查一次请求
+    const POLL: Duration = Duration::from_secs(1);
+
+    pub(crate) fn spawn(inner: Arc<QcPoolInner>) -> Self {
+        let pool = Arc::clone(&inner);
+        let handle = thread::Builder::new()
+            .name("qc-read-ahead".to_string())
+            .spawn(move || {
+                while !pool.read_ahead_signal().wait(Self::POLL) {
+                    // -- 预读只是提示, 失败时由之后的fetch报告
+                    while let Some(first) = pool.take_read_ahead() {
+                        let _ = pool.read_ahead_window(first);
+                    }
+                }
+            })
+            .expect("spawn read ahead");
+
+        QcReadAhead {
+            inner,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for QcReadAhead {
+    fn drop(&mut self) {
+        self.inner.read_ahead_signal().stop();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+use crate::{error::QcBupoError, page_guard::WritePageGuard, trace::PageId, wal::{Lsn, QcLogBody, QcLogManager, TxnId, INVALID_LSN}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QcTxnStatus {
+    Running,
+    Committed,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct QcRecoveryReport {
+    pub redone: usize,              // 重做的记录数
+    pub undone: usize,              // 回滚的Update数(写入的CLR数)
+    pub losers: Vec<TxnId>,         // 被回滚的事务
+    pub max_txn: TxnId,             // 日志中出现过的最大事务id
+}
+
+// -- ARIES恢复: 分析 -> 重做 -> 回滚
+//      page经由QcBuffpool取出并正常标记dirty, 恢复结束时日志已落盘, page留在pool中按正常流程写回
+pub struct QcRecovery<'l> {
+    log: &'l QcLogManager,
+    checkpoint: Lsn,                            // 分析的起点, INVALID_LSN为日志开头
+    att: HashMap<TxnId, (Lsn, QcTxnStatus)>,   // 活跃事务表: 最后一条记录与状态
+    dpt: BTreeMap<PageId, Lsn>,                // 脏页表: 第一次弄脏page的记录(rec lsn)
+    freed: HashMap<PageId, Lsn>,               // page最后一次被释放的记录, 之前的修改不再重做或回滚
+    report: QcRecoveryReport,
+}
+
+impl<'l> QcRecovery<'l> {
+    pub fn new(log: &'l QcLogManager, checkpoint: Lsn) -> Self {
+        QcRecovery {
+            log,
+            checkpoint,
+            att: HashMap::new(),
+            dpt: BTreeMap::new(),
+            freed: HashMap::new(),
+            report: QcRecoveryReport::default(),
+        }
+    }
+
+    // -- fetch: 按page id取得page的写guard
+    pub fn run<'a, F>(mut self, fetch: F) -> Result<QcRecoveryReport, QcBupoError>
+    where
+        F: Fn(PageId) -> Result<WritePageGuard<'a>, QcBupoError>,
+    {
+        self.analysis()?;
+        self.redo(&fetch)?;
+        self.undo(&fetch)?;
+        self.log.flush()?;
+
+        return Ok(self.report);
+    }
+
+    // -- 从checkpoint begin开始扫描; 遇到end时合并其中的表, 以begin之后的记录为准
+    fn analysis(&mut self) -> Result<(), QcBupoError> {
+        self.report.max_txn = self.log.max_txn();
+
+        let mut ended = HashSet::new();
+        for rec in self.log.iter_from(self.checkpoint)? {
+            let rec = rec?;
+            match rec.body {
+                QcLogBody::CheckpointBegin => continue,
+                QcLogBody::CheckpointEnd { dpt, att } => {
+                    for (pid, rec_lsn) in dpt {
+                        let ent = self.dpt.entry(pid).or_insert(rec_lsn);
+                        *ent = (*ent).min(rec_lsn);
+                    }
+                    for te in att {
+                        if ended.contains(&te.txn_id) {
+                            continue;
+                        }
+                        let status = if te.committed { QcTxnStatus::Committed } else { QcTxnStatus::Running };
+                        let ent = self.att.entry(te.txn_id).or_insert((te.last_lsn, status));
+                        if te.committed {
+                            ent.1 = QcTxnStatus::Committed;
+                        }
+                    }
+                    continue;
+                }
+                QcLogBody::End => {
+                    self.att.remove(&rec.txn_id);
+                    ended.insert(rec.txn_id);
+                    continue;
+                }
+                QcLogBody::Free { .. } => continue,
+                _ => {}
+            }
+
+            let entry = self.att.entry(rec.txn_id).or_insert((INVALID_LSN, QcTxnStatus::Running));
+            entry.0 = rec.lsn;
+            if let QcLogBody::Commit = rec.body {
+                entry.1 = QcTxnStatus::Committed;
+            }
+
+            if let Some(pid) = rec.body.page_id() {
+                self.dpt.entry(pid).or_insert(rec.lsn);
+            }
+        }
+
+        return Ok(());
+    }
+
+    // -- 从最小的rec lsn开始重放, page LSN不小于记录LSN的说明已写回过
+    //      释放后的page LSN为0, 释放之前的记录要跳过, 否则会覆盖空闲page的链接
+    fn redo<'a, F>(&mut self, fetch: &F) -> Result<(), QcBupoError>
+    where
+        F: Fn(PageId) -> Result<WritePageGuard<'a>, QcBupoError>,
+    {
+        let Some(&start) = self.dpt.values().min() else {
+            return Ok(());
+        };
+
+        // -- 释放记录可能早于checkpoint, 先从redo起点扫一遍
+        for rec in self.log.iter_from(start)? {
+            let rec = rec?;
+            if let QcLogBody::Free { page_id } = rec.body {
+                self.freed.insert(page_id, rec.lsn);
+            }
+        }
+
+        for rec in self.log.iter_from(start)? {
+            let rec = rec?;
+            let (page_id, offset, after) = match &rec.body {
+                QcLogBody::Update { page_id, offset, after, .. } => (*page_id, *offset, after),
+                QcLogBody::Clr { page_id, offset, after, .. } => (*page_id, *offset, after),
+                _ => continue,
+            };
+
+            match self.dpt.get(&page_id) {
+                Some(&rec_lsn) if rec.lsn >= rec_lsn => {}
+                _ => continue,
+            }
+            if self.is_freed(page_id, rec.lsn) {
+                continue;
+            }
+
+            let mut wg = fetch(page_id)?;
+            if wg.page_lsn() >= rec.lsn {
+                continue;
+            }
+
+            let off = offset as usize;
+            wg.mut_buffer()[off..off + after.len()].copy_from_slice(after);
+            wg.set_page_lsn(rec.lsn);
+            self.report.redone += 1;
+        }
+
+        return Ok(());
+    }
+
+    // -- 按LSN从大到小回滚未提交的事务, 每回滚一条Update写一条CLR
+    //      已提交但没有End的事务只补写End
+    fn undo<'a, F>(&mut self, fetch: &F) -> Result<(), QcBupoError>
+    where
+        F: Fn(PageId) -> Result<WritePageGuard<'a>, QcBupoError>,
+    {
+        let mut todo = BinaryHeap::new();
+        let mut last = HashMap::new();
+        for (&txn, &(lsn, status)) in self.att.iter() {
+            if status == QcTxnStatus::Committed {
+                self.log.append(txn, lsn, QcLogBody::End)?;
+                continue;
+            }
+
+            self.report.losers.push(txn);
+            last.insert(txn, lsn);
+            todo.push((lsn, txn));
+        }
+        self.report.losers.sort_unstable();
+
+        while let Some((lsn, txn)) = todo.pop() {
+            let Some(rec) = self.log.read(lsn)? else {
+                return Err(QcBupoError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("missing log record at {lsn}"))));
+            };
+
+            let next = match rec.body {
+                QcLogBody::Update { page_id, .. } if self.is_freed(page_id, rec.lsn) => rec.prev_lsn,
+                QcLogBody::Update { page_id, offset, before, .. } => {
+                    match fetch(page_id) {
+                        // -- 释放记录早于redo起点时没有扫到, 此时page仍是空闲的
+                        Err(QcBupoError::InvalidPage) => {}
+                        Err(e) => return Err(e),
+                        Ok(mut wg) => {
+                            let clr = self.log.append(txn, last[&txn], QcLogBody::Clr {
+                                page_id,
+                                offset,
+                                after: before.clone(),
+                                undo_next: rec.prev_lsn,
+                            })?;
+                            last.insert(txn, clr);
+
+                            let off = offset as usize;
+                            wg.mut_buffer()[off..off + before.len()].copy_from_slice(&before);
+                            wg.set_page_lsn(clr);
+                            self.report.undone += 1;
+                        }
+                    }
+                    rec.prev_lsn
+                }
+                QcLogBody::Clr { undo_next, .. } => undo_next,
+                _ => rec.prev_lsn,
+            };
+
+            if next == INVALID_LSN {
+                self.log.append(txn, last[&txn], QcLogBody::End)?;
+            } else {
+                todo.push((next, txn));
+            }
+        }
+
+        return Ok(());
+    }
+
+    // -- lsn处对page的修改之后page被释放过
+    fn is_freed(&self, page_id: PageId, lsn: Lsn) -> bool {
+        return self.freed.get(&page_id).is_some_and(|&freed| freed > lsn);
+    }
+}
@@ -0,0 +1,17 @@
+use crate::trace::PageId;
+
+pub type FrameId = usize;
+
+// -- 页面淘汰策略, QcBuffpool 通过它挑选 victim frame
+//      record_access: frame 被访问(page_id 供需要 ghost 记录的策略使用)
+//      set_evictable: pin 归零 / 重新 pin 时切换
+//      evict:         挑选并移除一个可淘汰的 frame
+//      remove:        frame 被释放, 不再跟踪
+//      size:          当前可淘汰的 frame 数
+pub trait Replacer: Send {
+    fn record_access(&mut self, frame_id: FrameId, page_id: PageId);
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool);
+    fn evict(&mut self) -> Option<FrameId>;
+    fn remove(&mut self, frame_id: FrameId);
+    fn size(&self) -> usize;
+}
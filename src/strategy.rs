@@ -0,0 +1,52 @@
+use crate::{replacer::FrameId, trace::PageId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QcStrategyKind {
+    // -- 大范围扫描: ring中dirty的frame留给pool处理, 另取frame
+    BulkRead,
+    // -- 批量写入: ring中dirty的frame先写回再复用
+    BulkWrite,
+}
+
+// -- 访问策略: 调用者私有的一小圈frame, 未命中时循环复用
+//      扫描过的page只占用ring大小的frame, 不会把pool中的热page挤掉
+#[derive(Debug)]
+pub struct QcAccessStrategy {
+    pub(crate) kind: QcStrategyKind,
+    pub(crate) ring: Vec<Option<(FrameId, PageId)>>,
+    pub(crate) cur: usize,
+}
+
+impl QcAccessStrategy {
+    pub fn new(kind: QcStrategyKind, ring_size: usize) -> Self {
+        assert!(ring_size > 0, "ring size must be positive");
+
+        QcAccessStrategy {
+            kind,
+            ring: vec![None; ring_size],
+            cur: 0,
+        }
+    }
+
+    pub fn bulk_read(ring_size: usize) -> Self {
+        Self::new(QcStrategyKind::BulkRead, ring_size)
+    }
+
+    pub fn bulk_write(ring_size: usize) -> Self {
+        Self::new(QcStrategyKind::BulkWrite, ring_size)
+    }
+
+    pub fn kind(&self) -> QcStrategyKind {
+        self.kind
+    }
+
+    // -- 移到下一个位置, 返回其中记录的frame
+    pub(crate) fn advance(&mut self) -> Option<(FrameId, PageId)> {
+        self.cur = (self.cur + 1) % self.ring.len();
+        self.ring[self.cur]
+    }
+
+    pub(crate) fn record(&mut self, frame_id: FrameId, page_id: PageId) {
+        self.ring[self.cur] = Some((frame_id, page_id));
+    }
+}
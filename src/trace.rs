@@ -1,64 +1,103 @@
 use std::collections::HashMap;
-use crate::double_link::{parse_qctd, QcDoubleLink, QcTd};
+use crate::double_link::{QcDoubleLink, QcTd};
+use crate::replacer::{FrameId, Replacer};
 
-pub type PageId = u8;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PageId(pub u64);
 
+impl PageId {
+    // -- 无效page, 不会被分配
+    pub const INVALID: PageId = PageId(u64::MAX);
+
+    pub fn is_valid(&self) -> bool {
+        *self != Self::INVALID
+    }
+}
+
+impl std::fmt::Display for PageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_valid() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "<invalid>")
+        }
+    }
+}
+
+// -- LRU: 链表中只保存可淘汰的 frame, 越靠前越久未使用
 #[derive(Debug)]
 pub struct QcTracer {
-    dblink: QcDoubleLink,
-    pmap: HashMap<PageId, QcTd>,
+    capacity: usize,
+    dblink: QcDoubleLink<FrameId>,
+    pmap: HashMap<FrameId, QcTd<FrameId>>,
 }
 
 impl QcTracer {
-    const MAX_SIZE: usize = 4;
-
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         QcTracer {
+            capacity,
             dblink: QcDoubleLink::new(),
             pmap: HashMap::new(),
         }
     }
 
-    pub fn insert(&mut self, page_id: u8) -> Option<()> {
-        let vlen = self.len();
-
-        if vlen >= Self::MAX_SIZE {
-            let Some(vv) = self.victim() else {
-                return None;
-            };
+    pub fn report(&self) {
+        self.dblink.report();
+        // println!("double link: {:?}", self.dblink);
+        println!("hash map: {:?}", self.pmap);
+    }
 
-            println!("victim: {vv}");
+    fn touch(&mut self, frame_id: FrameId) {
+        if let Some(pkv) = self.pmap.remove(&frame_id) {
+            self.dblink.remove_item(pkv);
         }
 
+        let qc = self.dblink.push_back(frame_id);
+        self.pmap.insert(frame_id, qc);
+    }
+}
+
+// -- pmap 中的节点指针只指向自己的 dblink, 两者一起转移
+unsafe impl Send for QcTracer {}
+
+impl Replacer for QcTracer {
+    fn record_access(&mut self, frame_id: FrameId, _page_id: PageId) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
         // move to back if exist
-        if let Some(&pkv) = self.pmap.get(&page_id) {
-            QcDoubleLink::remove_item(pkv);
-            self.pmap.remove(&page_id);
+        if self.pmap.contains_key(&frame_id) {
+            self.touch(frame_id);
         }
+    }
 
-        let qc = self.dblink.push_back(page_id.into());
-        self.pmap.insert(page_id, qc);
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        assert!(frame_id < self.capacity, "frame id out of range");
 
-        return Some(());
+        if evictable {
+            if !self.pmap.contains_key(&frame_id) {
+                self.touch(frame_id);
+            }
+        } else {
+            self.remove(frame_id);
+        }
     }
 
-    pub fn victim(&mut self) -> Option<u8> {
-        let Some(ov)= parse_qctd(self.dblink.pop_front()) else {
+    fn evict(&mut self) -> Option<FrameId> {
+        let Some(fid) = self.dblink.pop_front() else {
             return None;
         };
 
-        let pid = ov as u8;
-        self.pmap.remove(&pid);
-        return Some(pid);
+        self.pmap.remove(&fid);
+        return Some(fid);
     }
 
-    pub fn len(&self) -> usize {
-        return self.dblink.len();
+    fn remove(&mut self, frame_id: FrameId) {
+        if let Some(pkv) = self.pmap.remove(&frame_id) {
+            self.dblink.remove_item(pkv);
+        }
     }
 
-    pub fn report(&self) {
-        self.dblink.report();
-        // println!("double link: {:?}", self.dblink);
-        println!("hash map: {:?}", self.pmap);
+    fn size(&self) -> usize {
+        return self.dblink.len();
     }
 }
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use crate::double_link::{QcDoubleLink, QcTd};
+use crate::replacer::{FrameId, Replacer};
+use crate::trace::PageId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QcTwoQueueList {
+    A1in,
+    Am,
+}
+
+#[derive(Debug)]
+struct QcTwoQueueNode {
+    page_id: PageId,
+    list: QcTwoQueueList,
+    td: QcTd<FrameId>,
+    evictable: bool,
+}
+
+// -- 2Q: A1in 为首次访问的FIFO, Am 为再次访问的LRU
+//      A1out 记录从A1in淘汰的page, 再次访问时直接进入Am
+//      队列头为最旧
+#[derive(Debug)]
+pub struct QcTwoQueueReplacer {
+    capacity: usize,
+    kin: usize,
+    kout: usize,
+    evictable_num: usize,
+    a1in: QcDoubleLink<FrameId>,
+    a1out: QcDoubleLink<PageId>,
+    am: QcDoubleLink<FrameId>,
+    nodes: HashMap<FrameId, QcTwoQueueNode>,
+    ghosts: HashMap<PageId, QcTd<PageId>>,
+}
+
+impl QcTwoQueueReplacer {
+    // -- kin_ratio/kout_ratio 为A1in/A1out占pool大小的比例
+    pub fn new(capacity: usize, kin_ratio: f64, kout_ratio: f64) -> Self {
+        assert!((0.0..=1.0).contains(&kin_ratio), "kin ratio out of range");
+        assert!(kout_ratio >= 0.0, "kout ratio out of range");
+
+        QcTwoQueueReplacer {
+            capacity,
+            kin: ((capacity as f64 * kin_ratio) as usize).max(1),
+            kout: ((capacity as f64 * kout_ratio) as usize).max(1),
+            evictable_num: 0,
+            a1in: QcDoubleLink::new(),
+            a1out: QcDoubleLink::new(),
+            am: QcDoubleLink::new(),
+            nodes: HashMap::new(),
+            ghosts: HashMap::new(),
+        }
+    }
+
+    pub fn report(&self) {
+        println!("kin: {}, kout: {}", self.kin, self.kout);
+        print!("a1in: ");
+        self.a1in.report();
+        print!("a1out: ");
+        self.a1out.report();
+        print!("am: ");
+        self.am.report();
+    }
+
+    fn evict_from(&mut self, which: QcTwoQueueList) -> Option<FrameId> {
+        let nodes = &self.nodes;
+        let list = match which {
+            QcTwoQueueList::A1in => &mut self.a1in,
+            QcTwoQueueList::Am => &mut self.am,
+        };
+
+        let td = list.find(|fid| nodes[fid].evictable);
+        let Some(fid) = list.remove_item(td) else {
+            return None;
+        };
+
+        let nd = self.nodes.remove(&fid).unwrap();
+        self.evictable_num -= 1;
+
+        // -- 只有A1in淘汰的page进入ghost
+        if which == QcTwoQueueList::A1in {
+            if self.a1out.len() >= self.kout {
+                if let Some(old) = self.a1out.pop_front() {
+                    self.ghosts.remove(&old);
+                }
+            }
+            let gtd = self.a1out.push_back(nd.page_id);
+            self.ghosts.insert(nd.page_id, gtd);
+        }
+
+        return Some(fid);
+    }
+}
+
+// -- nodes/ghosts 中的节点指针只指向自己的队列, 一起转移
+unsafe impl Send for QcTwoQueueReplacer {}
+
+impl Replacer for QcTwoQueueReplacer {
+    fn record_access(&mut self, frame_id: FrameId, page_id: PageId) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        if let Some(nd) = self.nodes.get_mut(&frame_id) {
+            // -- A1in 中再次访问不调整位置
+            if nd.list == QcTwoQueueList::Am {
+                self.am.remove_item(nd.td);
+                nd.td = self.am.push_back(frame_id);
+            }
+            return;
+        }
+
+        let (list, td) = if let Some(gtd) = self.ghosts.remove(&page_id) {
+            self.a1out.remove_item(gtd);
+            (QcTwoQueueList::Am, self.am.push_back(frame_id))
+        } else {
+            (QcTwoQueueList::A1in, self.a1in.push_back(frame_id))
+        };
+
+        self.nodes.insert(frame_id, QcTwoQueueNode {
+            page_id,
+            list,
+            td,
+            evictable: false,
+        });
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        assert!(frame_id < self.capacity, "frame id out of range");
+
+        let Some(nd) = self.nodes.get_mut(&frame_id) else {
+            return;
+        };
+
+        if nd.evictable != evictable {
+            nd.evictable = evictable;
+            if evictable {
+                self.evictable_num += 1;
+            } else {
+                self.evictable_num -= 1;
+            }
+        }
+    }
+
+    fn evict(&mut self) -> Option<FrameId> {
+        let (first, second) = if self.a1in.len() > self.kin {
+            (QcTwoQueueList::A1in, QcTwoQueueList::Am)
+        } else {
+            (QcTwoQueueList::Am, QcTwoQueueList::A1in)
+        };
+
+        if let Some(fid) = self.evict_from(first) {
+            return Some(fid);
+        }
+
+        return self.evict_from(second);
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        let Some(nd) = self.nodes.remove(&frame_id) else {
+            return;
+        };
+
+        match nd.list {
+            QcTwoQueueList::A1in => self.a1in.remove_item(nd.td),
+            QcTwoQueueList::Am => self.am.remove_item(nd.td),
+        };
+        if nd.evictable {
+            self.evictable_num -= 1;
+        }
+    }
+
+    fn size(&self) -> usize {
+        return self.evictable_num;
+    }
+}
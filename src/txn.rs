@@ -0,0 +1,235 @@
+use std::{collections::{BTreeSet, HashMap}, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}};
+
+use crate::{error::QcBupoError, page::QcPager, page_guard::WritePageGuard, trace::PageId, wal::{Lsn, QcLogBody, QcLogManager, TxnId, INVALID_LSN}};
+
+// -- 事务句柄, commit/abort时消耗掉
+#[derive(Debug)]
+pub struct QcTransaction {
+    id: TxnId,
+}
+
+impl QcTransaction {
+    pub fn id(&self) -> TxnId {
+        self.id
+    }
+}
+
+#[derive(Debug, Default)]
+struct QcTxnState {
+    last_lsn: Lsn,
+    write_set: BTreeSet<PageId>,
+    // -- 持有的page锁
+    locks: BTreeSet<PageId>,
+}
+
+// -- 分配事务id, 记录每个事务的日志链与修改过的page
+//      写page前先加page锁, 持有到commit/abort结束; 锁被其他事务持有时不等待, 直接返回TxnConflict
+pub struct QcTransactionManager {
+    log: Arc<QcLogManager>,
+    next_txn: AtomicU64,
+    active: Mutex<HashMap<TxnId, QcTxnState>>,
+    // -- page锁表: page -> 持有锁的事务; 与active不同时持有
+    locks: Mutex<HashMap<PageId, TxnId>>,
+}
+
+impl QcTransactionManager {
+    // -- 相邻的修改区间间隔小于该值时合并为一条日志
+    const MERGE_GAP: usize = 16;
+
+    // -- 事务id接着日志中已有的最大id分配
+    pub fn new(log: Arc<QcLogManager>) -> Self {
+        let next = log.max_txn() + 1;
+        QcTransactionManager {
+            log,
+            next_txn: AtomicU64::new(next),
+            active: Mutex::new(HashMap::new()),
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn begin(&self) -> Result<QcTransaction, QcBupoError> {
+        let id = self.next_txn.fetch_add(1, Ordering::Relaxed);
+        let lsn = self.log.append(id, INVALID_LSN, QcLogBody::Begin)?;
+
+        self.active.lock().unwrap().insert(id, QcTxnState {
+            last_lsn: lsn,
+            write_set: BTreeSet::new(),
+            locks: BTreeSet::new(),
+        });
+
+        return Ok(QcTransaction { id });
+    }
+
+    // -- 在事务中修改page: f前后对比page内容, 变化的区间记为Update日志并设置page LSN
+    //      QcPager::save 等修改都经由这里才能被回滚; 写日志失败则恢复page并返回错误
+    //      page锁被其他事务持有时f不会执行, 返回TxnConflict
+    pub fn modify<R, F>(&self, txn: &QcTransaction, wg: &mut WritePageGuard<'_>, f: F) -> Result<R, QcBupoError>
+    where
+        F: FnOnce(&mut QcPager) -> R,
+    {
+        self.lock_page(txn, wg.page_id())?;
+
+        let before: Box<[u8]> = wg.buffer().into();
+        let ret = f(wg);
+
+        let ranges = Self::diff(&before, wg.buffer());
+        if ranges.is_empty() {
+            return Ok(ret);
+        }
+
+        let mut active = self.active.lock().unwrap();
+        let ts = active.get_mut(&txn.id).expect("transaction is not active");
+
+        let prev = ts.last_lsn;
+        let mut last = prev;
+        for &(start, end) in ranges.iter() {
+            let body = QcLogBody::Update {
+                page_id: wg.page_id(),
+                offset: start as u16,
+                before: before[start..end].to_vec(),
+                after: wg.buffer()[start..end].to_vec(),
+            };
+
+            match self.log.append(txn.id, last, body) {
+                Ok(lsn) => last = lsn,
+                Err(e) => {
+                    // -- 已写的日志留给abort回滚, 没写日志的部分直接还原
+                    wg.mut_buffer()[start..].copy_from_slice(&before[start..]);
+                    if last != prev {
+                        ts.last_lsn = last;
+                        ts.write_set.insert(wg.page_id());
+                        wg.set_page_lsn(last);
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+
+        ts.last_lsn = last;
+        ts.write_set.insert(wg.page_id());
+        wg.set_page_lsn(last);
+
+        return Ok(ret);
+    }
+
+    // -- 提交: 日志落盘后才返回并释放page锁
+    //      出错时锁不释放, 事务的结果由重启后的恢复决定
+    pub fn commit(&self, txn: QcTransaction) -> Result<(), QcBupoError> {
+        let ts = self.finish(&txn);
+
+        let lsn = self.log.append(txn.id, ts.last_lsn, QcLogBody::Commit)?;
+        self.log.flush_to(lsn)?;
+        self.log.append(txn.id, lsn, QcLogBody::End)?;
+
+        self.unlock_pages(&ts.locks);
+        return Ok(());
+    }
+
+    // -- 回滚: 沿日志链逐条还原前镜像并写CLR, 完成后释放page锁; 调用前需释放该事务持有的page guard
+    //      fetch 取page的写guard, 与恢复过程一样, 单个或并行的buffer pool都可用
+    //      中途失败时锁不释放, 剩下的部分由恢复过程回滚
+    pub fn abort<'a, F>(&self, txn: QcTransaction, fetch: F) -> Result<(), QcBupoError>
+    where
+        F: Fn(PageId) -> Result<WritePageGuard<'a>, QcBupoError>,
+    {
+        let ts = self.finish(&txn);
+
+        let mut last = self.log.append(txn.id, ts.last_lsn, QcLogBody::Abort)?;
+        let mut next = ts.last_lsn;
+        while next != INVALID_LSN {
+            let Some(rec) = self.log.read(next)? else {
+                return Err(QcBupoError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("missing log record at {next}"))));
+            };
+
+            next = match rec.body {
+                QcLogBody::Update { page_id, offset, before, .. } => {
+                    let mut wg = fetch(page_id)?;
+                    last = self.log.append(txn.id, last, QcLogBody::Clr {
+                        page_id,
+                        offset,
+                        after: before.clone(),
+                        undo_next: rec.prev_lsn,
+                    })?;
+
+                    let off = offset as usize;
+                    wg.mut_buffer()[off..off + before.len()].copy_from_slice(&before);
+                    wg.set_page_lsn(last);
+                    rec.prev_lsn
+                }
+                QcLogBody::Clr { undo_next, .. } => undo_next,
+                _ => rec.prev_lsn,
+            };
+        }
+
+        self.log.append(txn.id, last, QcLogBody::End)?;
+
+        self.unlock_pages(&ts.locks);
+        return Ok(());
+    }
+
+    // -- 加page锁, 已持有时直接返回; 不等待, 避免事务间死锁
+    fn lock_page(&self, txn: &QcTransaction, page_id: PageId) -> Result<(), QcBupoError> {
+        {
+            let mut locks = self.locks.lock().unwrap();
+            match locks.get(&page_id) {
+                Some(&owner) if owner == txn.id => return Ok(()),
+                Some(_) => return Err(QcBupoError::TxnConflict(page_id)),
+                None => locks.insert(page_id, txn.id),
+            };
+        }
+
+        let mut active = self.active.lock().unwrap();
+        active.get_mut(&txn.id).expect("transaction is not active").locks.insert(page_id);
+        return Ok(());
+    }
+
+    fn unlock_pages(&self, pages: &BTreeSet<PageId>) {
+        let mut locks = self.locks.lock().unwrap();
+        for pid in pages.iter() {
+            locks.remove(pid);
+        }
+    }
+
+    // -- 结束时移出活跃表, 之后不再持有active锁, 回滚取page时不会与modify互相等待
+    fn finish(&self, txn: &QcTransaction) -> QcTxnState {
+        return self.active.lock().unwrap()
+            .remove(&txn.id)
+            .expect("transaction is not active");
+    }
+
+    // -- 事务修改过的page
+    pub fn write_set(&self, txn: &QcTransaction) -> Vec<PageId> {
+        let active = self.active.lock().unwrap();
+        return active.get(&txn.id)
+            .map(|ts| ts.write_set.iter().copied().collect())
+            .unwrap_or_default();
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.active.lock().unwrap().len()
+    }
+
+    // -- 内容不同的区间 [start, end), 间隔较近的合并
+    fn diff(old: &[u8], new: &[u8]) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut i = 0;
+        while i < old.len() {
+            if old[i] == new[i] {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < old.len() && old[i] != new[i] {
+                i += 1;
+            }
+
+            match ranges.last_mut() {
+                Some(last) if start - last.1 < Self::MERGE_GAP => last.1 = i,
+                _ => ranges.push((start, i)),
+            }
+        }
+
+        return ranges;
+    }
+}
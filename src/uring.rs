@@ -0,0 +1,302 @@
+use std::{collections::HashMap, io, os::fd::{AsRawFd, FromRawFd, OwnedFd}, path::Path, sync::{atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, Arc, Condvar, Mutex}, thread::{self, JoinHandle}, time::Duration};
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+use crate::{disk::{DiskManager, QcFileDisk}, trace::PageId, wal::Lsn};
+
+// -- 一个ring: 提交方持sq锁写入并提交后即释放, 不等待完成
+//      reaper线程收取完成事件按user_data放入done, 等待方各自取走自己的结果
+struct QcRing {
+    ring: IoUring,
+    // -- 注册到ring的eventfd: 有完成事件时可读, reaper阻塞在它上面; drop时写入它唤醒reaper
+    efd: OwnedFd,
+    sq: Mutex<()>,
+    done: Mutex<HashMap<u64, i32>>,
+    cond: Condvar,
+    next_id: AtomicU64,
+    // -- 提交失败后不再使用: 没被内核取走的entry还留在队列中
+    broken: AtomicBool,
+    stop: AtomicBool,
+}
+
+impl QcRing {
+    fn new(entries: u32) -> io::Result<Self> {
+        let ring = IoUring::new(entries)?;
+        let raw = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let efd = unsafe { OwnedFd::from_raw_fd(raw) };
+        ring.submitter().register_eventfd(efd.as_raw_fd())?;
+
+        return Ok(QcRing {
+            ring,
+            efd,
+            sq: Mutex::new(()),
+            done: Mutex::new(HashMap::new()),
+            cond: Condvar::new(),
+            next_id: AtomicU64::new(0),
+            broken: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+        });
+    }
+
+    fn reap(&self) {
+        loop {
+            // -- 先读eventfd再收取: 之后到达的完成事件会让下一次读立即返回; 出错时退化为轮询
+            let mut cnt = 0_u64;
+            let n = unsafe { libc::read(self.efd.as_raw_fd(), &mut cnt as *mut u64 as *mut libc::c_void, 8) };
+            if n < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let mut done = self.done.lock().unwrap();
+            for cqe in unsafe { self.ring.completion_shared() } {
+                done.insert(cqe.user_data(), cqe.result());
+            }
+            drop(done);
+            self.cond.notify_all();
+
+            if self.stop.load(Ordering::Acquire) {
+                return;
+            }
+        }
+    }
+
+    // -- 不经过ring唤醒reaper, 弃用的ring也能唤醒
+    fn wake(&self) {
+        let one = 1_u64;
+        unsafe {
+            libc::write(self.efd.as_raw_fd(), &one as *const u64 as *const libc::c_void, 8);
+        }
+    }
+
+    // -- 写入一段entry并提交到全部被内核取走, 返回取走的个数与出错时的错误
+    fn submit(&self, entries: &[squeue::Entry], base: u64) -> (usize, Option<io::Error>) {
+        let _sq = self.sq.lock().unwrap();
+        let cap = self.ring.params().sq_entries() as usize;
+
+        // -- 持sq锁期间队列中只有本次的entry, 释放前都已被取走或ring已弃用
+        let mut accepted = 0;
+        for (ci, chunk) in entries.chunks(cap).enumerate() {
+            {
+                let mut sq = unsafe { self.ring.submission_shared() };
+                for (i, et) in chunk.iter().enumerate() {
+                    let et = et.clone().user_data(base + (ci * cap + i) as u64);
+                    if unsafe { sq.push(&et) }.is_err() {
+                        self.broken.store(true, Ordering::Release);
+                        return (accepted, Some(io::Error::other("io_uring submission queue overflow")));
+                    }
+                }
+            }
+
+            let mut left = chunk.len();
+            while left > 0 {
+                match self.ring.submit() {
+                    Ok(n) => {
+                        let n = n.min(left);
+                        left -= n;
+                        accepted += n;
+                    }
+                    // -- 完成队列满或暂时没有资源, 等reaper收取后重试
+                    Err(e) if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock)
+                        || e.raw_os_error() == Some(libc::EBUSY) => thread::yield_now(),
+                    Err(e) => {
+                        self.broken.store(true, Ordering::Release);
+                        return (accepted, Some(e));
+                    }
+                }
+            }
+        }
+
+        return (accepted, None);
+    }
+
+    // -- 等待 [base, base + count) 的结果
+    fn wait(&self, base: u64, count: usize) -> Vec<i32> {
+        let mut res = vec![0_i32; count];
+        let mut left = count;
+        let mut done = self.done.lock().unwrap();
+        loop {
+            for (i, r) in res.iter_mut().enumerate() {
+                if let Some(ret) = done.remove(&(base + i as u64)) {
+                    *r = ret;
+                    left -= 1;
+                }
+            }
+            if left == 0 {
+                return res;
+            }
+            done = self.cond.wait(done).unwrap();
+        }
+    }
+}
+
+// -- io_uring 存储后端: page读写批量提交到ring, 由内核并发完成
+//      header/free list 仍由 QcFileDisk 管理, 几个ring轮流使用, 多个线程可同时有IO在途
+//      每次调用都等本批完成才返回: 单个page的read_page/write_page与同步IO一样阻塞调用线程,
+//      只有批量写回(write_back)与预读(prefetch)能让一个线程的多个IO重叠
+pub struct QcUringDisk {
+    file: QcFileDisk,
+    rings: Vec<Arc<QcRing>>,
+    reapers: Vec<JoinHandle<()>>,
+    next: AtomicUsize,
+}
+
+impl QcUringDisk {
+    const RING_NUM: usize = 4;
+    const RING_ENTRIES: u32 = 64;
+
+    pub fn open<T: AsRef<Path>>(path: T, init_pages: u64) -> io::Result<Self> {
+        let file = QcFileDisk::open(path, init_pages)?;
+
+        let mut rings = Vec::new();
+        let mut reapers = Vec::new();
+        for _ in 0..Self::RING_NUM {
+            let ring = Arc::new(QcRing::new(Self::RING_ENTRIES)?);
+            let rp = Arc::clone(&ring);
+            reapers.push(thread::Builder::new()
+                .name("qc-uring-reaper".to_string())
+                .spawn(move || rp.reap())?);
+            rings.push(ring);
+        }
+
+        return Ok(QcUringDisk {
+            file,
+            rings,
+            reapers,
+            next: AtomicUsize::new(0),
+        });
+    }
+
+    // -- 轮流使用没有弃用的ring
+    fn ring(&self) -> io::Result<&QcRing> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.rings.len() {
+            let ring = &self.rings[(start + i) % self.rings.len()];
+            if !ring.broken.load(Ordering::Acquire) {
+                return Ok(ring);
+            }
+        }
+
+        return Err(io::Error::other("all io_uring rings failed"));
+    }
+
+    // -- 提交并等待全部完成, 返回每个请求的结果(字节数或-errno)
+    //      entry引用调用者的buffer, 已被内核取走的请求即使出错也要等完成才返回
+    fn submit_all(&self, entries: &[squeue::Entry]) -> io::Result<Vec<i32>> {
+        let ring = self.ring()?;
+        let base = ring.next_id.fetch_add(entries.len() as u64, Ordering::Relaxed);
+
+        let (accepted, err) = ring.submit(entries, base);
+        let res = ring.wait(base, accepted);
+        if let Some(e) = err {
+            return Err(e);
+        }
+
+        return Ok(res);
+    }
+
+    fn check(ret: i32) -> io::Result<usize> {
+        if ret < 0 {
+            return Err(io::Error::from_raw_os_error(-ret));
+        }
+
+        return Ok(ret as usize);
+    }
+}
+
+impl DiskManager for QcUringDisk {
+    fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> io::Result<()> {
+        return self.read_pages(&mut [(page_id, buf)]);
+    }
+
+    fn write_page(&self, page_id: PageId, buf: &[u8]) -> io::Result<()> {
+        return self.write_pages(&[(page_id, buf)]);
+    }
+
+    fn allocate_page(&self) -> io::Result<PageId> {
+        return self.file.allocate_page();
+    }
+
+    fn allocate_page_in(&self, num: u64, rem: u64) -> io::Result<PageId> {
+        return self.file.allocate_page_in(num, rem);
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> io::Result<()> {
+        return self.file.deallocate_page(page_id);
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        return self.file.sync();
+    }
+
+    fn is_allocated(&self, page_id: PageId) -> bool {
+        return self.file.is_allocated(page_id);
+    }
+
+    fn advise(&self, first: PageId, count: usize) {
+        self.file.advise(first, count);
+    }
+
+    fn checkpoint_lsn(&self) -> io::Result<Lsn> {
+        return self.file.checkpoint_lsn();
+    }
+
+    fn set_checkpoint_lsn(&self, lsn: Lsn) -> io::Result<()> {
+        return self.file.set_checkpoint_lsn(lsn);
+    }
+
+    fn read_pages(&self, reqs: &mut [(PageId, &mut [u8])]) -> io::Result<()> {
+        let fd = types::Fd(self.file.raw_fd());
+        let mut entries = Vec::with_capacity(reqs.len());
+        for (pid, buf) in reqs.iter_mut() {
+            entries.push(opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as u32)
+                .offset(QcFileDisk::offset(*pid)?)
+                .build());
+        }
+
+        let res = self.submit_all(&entries)?;
+        for ((pid, buf), ret) in reqs.iter_mut().zip(res) {
+            let n = Self::check(ret)?;
+            if n == 0 {
+                // -- 超出文件末尾
+                buf.fill(0);
+            } else if n < buf.len() {
+                // -- 短读很少见, 交给同步路径补齐
+                self.file.read_page(*pid, buf)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn write_pages(&self, reqs: &[(PageId, &[u8])]) -> io::Result<()> {
+        let fd = types::Fd(self.file.raw_fd());
+        let mut entries = Vec::with_capacity(reqs.len());
+        for (pid, buf) in reqs.iter() {
+            entries.push(opcode::Write::new(fd, buf.as_ptr(), buf.len() as u32)
+                .offset(QcFileDisk::offset(*pid)?)
+                .build());
+        }
+
+        let res = self.submit_all(&entries)?;
+        for ((pid, buf), ret) in reqs.iter().zip(res) {
+            if Self::check(ret)? < buf.len() {
+                self.file.write_page(*pid, buf)?;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl Drop for QcUringDisk {
+    fn drop(&mut self) {
+        for (ring, reaper) in self.rings.iter().zip(self.reapers.drain(..)) {
+            ring.stop.store(true, Ordering::Release);
+            ring.wake();
+            let _ = reaper.join();
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::{collections::HashMap, fs::{File, OpenOptions}, io, os::unix::fs::FileExt, path::Path, sync::Mutex};
+
+use crate::{checksum::crc32c, trace::PageId};
+
+// -- LSN: 日志记录在日志文件中的字节偏移, 0不是合法位置
+pub type Lsn = u64;
+pub const INVALID_LSN: Lsn = 0;
+
+pub type TxnId = u64;
+pub const INVALID_TXN: TxnId = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QcLogBody {
+    Begin,
+    Commit,
+    Abort,
+    // -- 事务结束(提交或回滚完成), 之后不再有它的记录
+    End,
+    // -- page中[offset, offset + len)的前后镜像
+    Update { page_id: PageId, offset: u16, before: Vec<u8>, after: Vec<u8> },
+    // -- 补偿记录: 回滚某条Update时写入, 只redo不undo
+    //      undo_next: 该事务下一条需要回滚的记录
+    Clr { page_id: PageId, offset: u16, after: Vec<u8>, undo_next: Lsn },
+    // -- fuzzy checkpoint: begin之后取脏页表与活跃事务表, 写在end中
+    CheckpointBegin,
+    CheckpointEnd { dpt: Vec<(PageId, Lsn)>, att: Vec<QcTxnEntry> },
+    // -- page被释放, 恢复时不再重做或回滚之前对它的修改
+    Free { page_id: PageId },
+}
+
+// -- 活跃事务表的一项
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QcTxnEntry {
+    pub txn_id: TxnId,
+    pub last_lsn: Lsn,
+    pub committed: bool,
+}
+
+impl QcLogBody {
+    const BEGIN: u8 = 1;
+    const COMMIT: u8 = 2;
+    const ABORT: u8 = 3;
+    const END: u8 = 4;
+    const UPDATE: u8 = 5;
+    const CLR: u8 = 6;
+    const CKPT_BEGIN: u8 = 7;
+    const CKPT_END: u8 = 8;
+    const FREE: u8 = 9;
+
+    // -- 修改的page, 不修改page的记录为None
+    pub fn page_id(&self) -> Option<PageId> {
+        match self {
+            QcLogBody::Update { page_id, .. } | QcLogBody::Clr { page_id, .. } => Some(*page_id),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            QcLogBody::Begin => out.push(Self::BEGIN),
+            QcLogBody::Commit => out.push(Self::COMMIT),
+            QcLogBody::Abort => out.push(Self::ABORT),
+            QcLogBody::End => out.push(Self::END),
+            QcLogBody::Update { page_id, offset, before, after } => {
+                assert_eq!(before.len(), after.len(), "before/after image length mismatch");
+                out.push(Self::UPDATE);
+                out.extend_from_slice(&page_id.0.to_be_bytes());
+                out.extend_from_slice(&offset.to_be_bytes());
+                out.extend_from_slice(&(before.len() as u16).to_be_bytes());
+                out.extend_from_slice(before);
+                out.extend_from_slice(after);
+            }
+            QcLogBody::Clr { page_id, offset, after, undo_next } => {
+                out.push(Self::CLR);
+                out.extend_from_slice(&page_id.0.to_be_bytes());
+                out.extend_from_slice(&offset.to_be_bytes());
+                out.extend_from_slice(&(after.len() as u16).to_be_bytes());
+                out.extend_from_slice(after);
+                out.extend_from_slice(&undo_next.to_be_bytes());
+            }
+            QcLogBody::CheckpointBegin => out.push(Self::CKPT_BEGIN),
+            QcLogBody::CheckpointEnd { dpt, att } => {
+                out.push(Self::CKPT_END);
+                out.extend_from_slice(&(dpt.len() as u32).to_be_bytes());
+                for (pid, rec_lsn) in dpt.iter() {
+                    out.extend_from_slice(&pid.0.to_be_bytes());
+                    out.extend_from_slice(&rec_lsn.to_be_bytes());
+                }
+                out.extend_from_slice(&(att.len() as u32).to_be_bytes());
+                for te in att.iter() {
+                    out.extend_from_slice(&te.txn_id.to_be_bytes());
+                    out.extend_from_slice(&te.last_lsn.to_be_bytes());
+                    out.push(te.committed as u8);
+                }
+            }
+            QcLogBody::Free { page_id } => {
+                out.push(Self::FREE);
+                out.extend_from_slice(&page_id.0.to_be_bytes());
+            }
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let (&kind, rest) = buf.split_first()?;
+        let body = match kind {
+            Self::BEGIN => QcLogBody::Begin,
+            Self::COMMIT => QcLogBody::Commit,
+            Self::ABORT => QcLogBody::Abort,
+            Self::END => QcLogBody::End,
+            Self::UPDATE | Self::CLR => {
+                if rest.len() < 12 {
+                    return None;
+                }
+                let page_id = PageId(u64::from_be_bytes(rest[0..8].try_into().unwrap()));
+                let offset = u16::from_be_bytes(rest[8..10].try_into().unwrap());
+                let len = u16::from_be_bytes(rest[10..12].try_into().unwrap()) as usize;
+                let img = &rest[12..];
+
+                if kind == Self::UPDATE {
+                    if img.len() != 2 * len {
+                        return None;
+                    }
+                    QcLogBody::Update {
+                        page_id,
+                        offset,
+                        before: img[..len].to_vec(),
+                        after: img[len..].to_vec(),
+                    }
+                } else {
+                    if img.len() != len + 8 {
+                        return None;
+                    }
+                    QcLogBody::Clr {
+                        page_id,
+                        offset,
+                        after: img[..len].to_vec(),
+                        undo_next: u64::from_be_bytes(img[len..].try_into().unwrap()),
+                    }
+                }
+            }
+            Self::CKPT_BEGIN => QcLogBody::CheckpointBegin,
+            Self::CKPT_END => Self::decode_checkpoint(rest)?,
+            Self::FREE => QcLogBody::Free {
+                page_id: PageId(u64::from_be_bytes(rest.try_into().ok()?)),
+            },
+            _ => return None,
+        };
+
+        return Some(body);
+    }
+
+    fn decode_checkpoint(buf: &[u8]) -> Option<Self> {
+        let u64_at = |off: usize| buf.get(off..off + 8).map(|b| u64::from_be_bytes(b.try_into().unwrap()));
+        let u32_at = |off: usize| buf.get(off..off + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize);
+
+        let mut off = 0;
+        let mut dpt = Vec::new();
+        for _ in 0..u32_at(off)? {
+            dpt.push((PageId(u64_at(off + 4)?), u64_at(off + 12)?));
+            off += 16;
+        }
+        off += 4;
+
+        let mut att = Vec::new();
+        for _ in 0..u32_at(off)? {
+            att.push(QcTxnEntry {
+                txn_id: u64_at(off + 4)?,
+                last_lsn: u64_at(off + 12)?,
+                committed: *buf.get(off + 20)? != 0,
+            });
+            off += 17;
+        }
+        off += 4;
+
+        if off != buf.len() {
+            return None;
+        }
+
+        return Some(QcLogBody::CheckpointEnd { dpt, att });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QcLogRecord {
+    pub lsn: Lsn,
+    pub txn_id: TxnId,
+    // -- 同一事务的上一条记录
+    pub prev_lsn: Lsn,
+    pub body: QcLogBody,
+}
+
+// -- 记录布局:
+//      [0~3]: 记录长度, [4~7]: crc32c([8~]), [8~15]: txn id, [16~23]: prev lsn, [24~]: body
+const RECORD_HEAD: usize = 24;
+
+fn encode_record(txn_id: TxnId, prev_lsn: Lsn, body: &QcLogBody, out: &mut Vec<u8>) {
+    let start = out.len();
+    out.extend_from_slice(&[0_u8; 8]);
+    out.extend_from_slice(&txn_id.to_be_bytes());
+    out.extend_from_slice(&prev_lsn.to_be_bytes());
+    body.encode(out);
+
+    let len = (out.len() - start) as u32;
+    let crc = crc32c(&out[start + 8..]);
+    out[start..start + 4].copy_from_slice(&len.to_be_bytes());
+    out[start + 4..start + 8].copy_from_slice(&crc.to_be_bytes());
+}
+
+// -- 长度或校验和不对(写了一半的尾部)返回None
+fn decode_record(lsn: Lsn, buf: &[u8]) -> Option<QcLogRecord> {
+    if buf.len() <= RECORD_HEAD {
+        return None;
+    }
+
+    let crc = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+    if crc != crc32c(&buf[8..]) {
+        return None;
+    }
+
+    return Some(QcLogRecord {
+        lsn,
+        txn_id: u64::from_be_bytes(buf[8..16].try_into().unwrap()),
+        prev_lsn: u64::from_be_bytes(buf[16..24].try_into().unwrap()),
+        body: QcLogBody::decode(&buf[RECORD_HEAD..])?,
+    });
+}
+
+struct QcLogBuffer {
+    buf: Vec<u8>,
+    // -- buf[0]对应的LSN, 之前的日志都已写入文件并sync
+    buf_lsn: Lsn,
+    // -- 还没有End的事务, 随append更新
+    active: HashMap<TxnId, QcTxnEntry>,
+    max_txn: TxnId,
+}
+
+// -- 追加写的日志文件, 记录先进入内存buffer, flush_to时写入并sync
+//      文件头: [0~3]: magic, [4~7]: 保留
+pub struct QcLogManager {
+    fd: File,
+    state: Mutex<QcLogBuffer>,
+}
+
+impl QcLogManager {
+    const MAGIC: &'static [u8; 4] = b"QCWL";
+    const FILE_HEAD: u64 = 8;
+    // -- buffer超过该大小时先刷盘
+    const BUFFER_SIZE: usize = 64 * 1024;
+
+    // -- 打开时丢弃写了一半的尾部记录
+    pub fn open<T: AsRef<Path>>(path: T) -> io::Result<Self> {
+        let fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref())?;
+
+        let flen = fd.metadata()?.len();
+        if flen < Self::FILE_HEAD {
+            let mut head = [0_u8; Self::FILE_HEAD as usize];
+            head[0..4].copy_from_slice(Self::MAGIC);
+            fd.write_all_at(&head, 0)?;
+            fd.set_len(Self::FILE_HEAD)?;
+            fd.sync_all()?;
+        } else {
+            let mut magic = [0_u8; 4];
+            fd.read_exact_at(&mut magic, 0)?;
+            if &magic != Self::MAGIC {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "not a log file"));
+            }
+        }
+
+        let log = QcLogManager {
+            fd,
+            state: Mutex::new(QcLogBuffer {
+                buf: Vec::new(),
+                buf_lsn: Self::FILE_HEAD,
+                active: HashMap::new(),
+                max_txn: INVALID_TXN,
+            }),
+        };
+
+        let mut end = Self::FILE_HEAD;
+        let mut max_txn = INVALID_TXN;
+        while let Some((rec, next)) = log.read_file(end)? {
+            max_txn = max_txn.max(rec.txn_id);
+            end = next;
+        }
+        if end < log.fd.metadata()?.len() {
+            log.fd.set_len(end)?;
+            log.fd.sync_all()?;
+        }
+
+        // -- 活跃事务由恢复过程补写End, 这里不重建
+        let mut st = log.state.lock().unwrap();
+        st.buf_lsn = end;
+        st.max_txn = max_txn;
+        drop(st);
+
+        return Ok(log);
+    }
+
+    // -- 追加一条记录, 返回其LSN; 只保证在内存中
+    pub fn append(&self, txn_id: TxnId, prev_lsn: Lsn, body: QcLogBody) -> io::Result<Lsn> {
+        let mut st = self.state.lock().unwrap();
+        if st.buf.len() >= Self::BUFFER_SIZE {
+            self.write_out(&mut st)?;
+        }
+
+        let lsn = st.buf_lsn + st.buf.len() as u64;
+        encode_record(txn_id, prev_lsn, &body, &mut st.buf);
+
+        if txn_id != INVALID_TXN {
+            st.max_txn = st.max_txn.max(txn_id);
+            if let QcLogBody::End = body {
+                st.active.remove(&txn_id);
+            } else {
+                let te = st.active.entry(txn_id).or_insert(QcTxnEntry { txn_id, last_lsn: lsn, committed: false });
+                te.last_lsn = lsn;
+                te.committed |= body == QcLogBody::Commit;
+            }
+        }
+
+        return Ok(lsn);
+    }
+
+    // -- 保证lsn及之前的记录都已落盘
+    pub fn flush_to(&self, lsn: Lsn) -> io::Result<()> {
+        let mut st = self.state.lock().unwrap();
+        if lsn < st.buf_lsn {
+            return Ok(());
+        }
+
+        return self.write_out(&mut st);
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        let mut st = self.state.lock().unwrap();
+        return self.write_out(&mut st);
+    }
+
+    // -- 已落盘的日志末尾, 小于它的LSN都已持久
+    pub fn flushed_lsn(&self) -> Lsn {
+        self.state.lock().unwrap().buf_lsn
+    }
+
+    // -- 下一条记录的LSN
+    pub fn next_lsn(&self) -> Lsn {
+        let st = self.state.lock().unwrap();
+        st.buf_lsn + st.buf.len() as u64
+    }
+
+    // -- 当前活跃事务表的快照, 按事务id排序
+    pub fn active_txns(&self) -> Vec<QcTxnEntry> {
+        let mut att: Vec<QcTxnEntry> = self.state.lock().unwrap().active.values().copied().collect();
+        att.sort_unstable_by_key(|te| te.txn_id);
+        return att;
+    }
+
+    // -- 日志中出现过的最大事务id, 包括打开前的
+    pub fn max_txn(&self) -> TxnId {
+        self.state.lock().unwrap().max_txn
+    }
+
+    // -- 读取lsn处的记录, 可以在buffer中
+    pub fn read(&self, lsn: Lsn) -> io::Result<Option<QcLogRecord>> {
+        {
+            let st = self.state.lock().unwrap();
+            if lsn >= st.buf_lsn {
+                let off = (lsn - st.buf_lsn) as usize;
+                return Ok(Self::read_buf(&st.buf, off).map(|(rec, _)| QcLogRecord { lsn, ..rec }));
+            }
+        }
+
+        return Ok(self.read_file(lsn)?.map(|(rec, _)| rec));
+    }
+
+    // -- 从lsn开始顺序读取, 先把buffer刷盘
+    pub fn iter_from(&self, lsn: Lsn) -> io::Result<QcLogIter<'_>> {
+        self.flush()?;
+
+        return Ok(QcLogIter {
+            log: self,
+            next: lsn.max(Self::FILE_HEAD),
+        });
+    }
+
+    fn write_out(&self, st: &mut QcLogBuffer) -> io::Result<()> {
+        if st.buf.is_empty() {
+            return Ok(());
+        }
+
+        self.fd.write_all_at(&st.buf, st.buf_lsn)?;
+        self.fd.sync_data()?;
+        st.buf_lsn += st.buf.len() as u64;
+        st.buf.clear();
+
+        return Ok(());
+    }
+
+    fn read_buf(buf: &[u8], off: usize) -> Option<(QcLogRecord, usize)> {
+        if off + 4 > buf.len() {
+            return None;
+        }
+
+        let len = u32::from_be_bytes(buf[off..off + 4].try_into().unwrap()) as usize;
+        if len <= RECORD_HEAD || off + len > buf.len() {
+            return None;
+        }
+
+        return decode_record(0, &buf[off..off + len]).map(|rec| (rec, len));
+    }
+
+    // -- 返回记录与下一条记录的LSN
+    fn read_file(&self, lsn: Lsn) -> io::Result<Option<(QcLogRecord, Lsn)>> {
+        let flen = self.fd.metadata()?.len();
+        if lsn < Self::FILE_HEAD || lsn + 4 > flen {
+            return Ok(None);
+        }
+
+        let mut head = [0_u8; 4];
+        self.fd.read_exact_at(&mut head, lsn)?;
+        let len = u32::from_be_bytes(head) as u64;
+        if len <= RECORD_HEAD as u64 || lsn + len > flen {
+            return Ok(None);
+        }
+
+        let mut buf = vec![0_u8; len as usize];
+        self.fd.read_exact_at(&mut buf, lsn)?;
+
+        return Ok(decode_record(lsn, &buf).map(|rec| (rec, lsn + len)));
+    }
+}
+
+pub struct QcLogIter<'a> {
+    log: &'a QcLogManager,
+    next: Lsn,
+}
+
+impl Iterator for QcLogIter<'_> {
+    type Item = io::Result<QcLogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.log.read_file(self.next) {
+            Ok(Some((rec, next))) => {
+                self.next = next;
+                Some(Ok(rec))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}